@@ -1,134 +1,337 @@
 //! Execute make of OpenBLAS, and its options
 
-use crate::{check::*, error::*};
+use crate::{builder::*, check::*, error::*, mode::*, stage::*, target::*, tuning::*};
+use serde::{Deserialize, Serialize};
 use std::{
-    fs,
+    convert::TryFrom,
+    env, fs,
     os::unix::io::*,
     path::*,
     process::{Command, Stdio},
 };
-use walkdir::WalkDir;
 
 /// Interface for 32-bit interger (LP64) and 64-bit integer (ILP64)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Interface {
     LP64,
     ILP64,
 }
 
-/// CPU list in [TargetList](https://github.com/xianyi/OpenBLAS/blob/v0.3.10/TargetList.txt)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[allow(non_camel_case_types)] // to use original identifiers
-pub enum Target {
-    // X86/X86_64 Intel
-    P2,
-    KATMAI,
-    COPPERMINE,
-    NORTHWOOD,
-    PRESCOTT,
-    BANIAS,
-    YONAH,
-    CORE2,
-    PENRYN,
-    DUNNINGTON,
-    NEHALEM,
-    SANDYBRIDGE,
-    HASWELL,
-    SKYLAKEX,
-    ATOM,
-
-    // X86/X86_64 AMD
-    ATHLON,
-    OPTERON,
-    OPTERON_SSE3,
-    BARCELONA,
-    SHANGHAI,
-    ISTANBUL,
-    BOBCAT,
-    BULLDOZER,
-    PILEDRIVER,
-    STEAMROLLER,
-    EXCAVATOR,
-    ZEN,
-
-    // X86/X86_64 generic
-    SSE_GENERIC,
-    VIAC3,
-    NANO,
-
-    // Power
-    POWER4,
-    POWER5,
-    POWER6,
-    POWER7,
-    POWER8,
-    POWER9,
-    PPCG4,
-    PPC970,
-    PPC970MP,
-    PPC440,
-    PPC440FP2,
-    CELL,
-
-    // MIPS
-    P5600,
-    MIPS1004K,
-    MIPS24K,
-
-    // MIPS64
-    SICORTEX,
-    LOONGSON3A,
-    LOONGSON3B,
-    I6400,
-    P6600,
-    I6500,
-
-    // IA64
-    ITANIUM2,
-
-    // Sparc
-    SPARC,
-    SPARCV7,
-
-    // ARM
-    CORTEXA15,
-    CORTEXA9,
-    ARMV7,
-    ARMV6,
-    ARMV5,
-
-    // ARM64
-    ARMV8,
-    CORTEXA53,
-    CORTEXA57,
-    CORTEXA72,
-    CORTEXA73,
-    NEOVERSEN1,
-    EMAG8180,
-    FALKOR,
-    THUNDERX,
-    THUNDERX2T99,
-    TSV110,
-
-    // System Z
-    ZARCH_GENERIC,
-    Z13,
-    Z14,
+/// Word size of the library (`BINARY`), `32` or `64` in TOML
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u32", into = "u32")]
+pub enum Binary {
+    B32,
+    B64,
+}
+
+impl From<Binary> for u32 {
+    fn from(binary: Binary) -> u32 {
+        match binary {
+            Binary::B32 => 32,
+            Binary::B64 => 64,
+        }
+    }
+}
+
+impl TryFrom<u32> for Binary {
+    type Error = String;
+    fn try_from(bits: u32) -> Result<Self, String> {
+        match bits {
+            32 => Ok(Binary::B32),
+            64 => Ok(Binary::B64),
+            _ => Err(format!("binary must be 32 or 64, but {}", bits)),
+        }
+    }
+}
+
+/// C compiler family, used as `CC` if `cc` is not specified
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CCompiler {
+    Gcc,
+    Clang,
+}
+
+impl CCompiler {
+    /// Command of the compiler, e.g. `clang`
+    pub fn command(&self) -> &'static str {
+        match self {
+            CCompiler::Gcc => "gcc",
+            CCompiler::Clang => "clang",
+        }
+    }
+
+    /// Parse lowercase command name, e.g. `clang`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gcc" => Some(CCompiler::Gcc),
+            "clang" => Some(CCompiler::Clang),
+            _ => None,
+        }
+    }
+}
+
+/// Fortran compiler family, used as `FC` if `fc` is not specified
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FortranCompiler {
+    GFortran,
+    /// LLVM Flang
+    #[serde(alias = "flang-new")]
+    Flang,
+}
+
+impl FortranCompiler {
+    /// Command of the compiler, e.g. `flang-new`
+    pub fn command(&self) -> &'static str {
+        match self {
+            FortranCompiler::GFortran => "gfortran",
+            FortranCompiler::Flang => "flang-new",
+        }
+    }
+
+    /// Parse lowercase name, `gfortran` or `flang` (also `flang-new`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gfortran" => Some(FortranCompiler::GFortran),
+            "flang" | "flang-new" => Some(FortranCompiler::Flang),
+            _ => None,
+        }
+    }
+}
+
+/// Implementation of LAPACK built into OpenBLAS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LapackBackend {
+    /// Reference LAPACK in Fortran, which requires a Fortran compiler
+    Fortran,
+    /// C translation of reference LAPACK bundled in OpenBLAS (`C_LAPACK=1`), no Fortran compiler is used
+    C,
+    /// Reference LAPACK in Fortran with recursive implementations of some routines by ReLAPACK (`BUILD_RELAPACK=1`)
+    ReLapack,
+}
+
+impl LapackBackend {
+    /// Lowercase name, e.g. `relapack`
+    pub fn name(&self) -> &'static str {
+        match self {
+            LapackBackend::Fortran => "fortran",
+            LapackBackend::C => "c",
+            LapackBackend::ReLapack => "relapack",
+        }
+    }
+
+    /// Parse lowercase name, `fortran`, `c` or `relapack`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fortran" => Some(LapackBackend::Fortran),
+            "c" => Some(LapackBackend::C),
+            "relapack" => Some(LapackBackend::ReLapack),
+            _ => None,
+        }
+    }
+}
+
+/// Precision of BLAS and LAPACK routines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// `s*` routines (`BUILD_SINGLE`)
+    Single,
+    /// `d*` routines (`BUILD_DOUBLE`)
+    Double,
+    /// `c*` routines (`BUILD_COMPLEX`), which also requires single precision
+    Complex,
+    /// `z*` routines (`BUILD_COMPLEX16`), which also requires double precision
+    Complex16,
+}
+
+impl Precision {
+    pub const ALL: [Precision; 4] = [
+        Precision::Single,
+        Precision::Double,
+        Precision::Complex,
+        Precision::Complex16,
+    ];
+
+    /// Lowercase name, e.g. `complex16`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precision::Single => "single",
+            Precision::Double => "double",
+            Precision::Complex => "complex",
+            Precision::Complex16 => "complex16",
+        }
+    }
+
+    /// Parse lowercase name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Precision::ALL.iter().find(|p| p.name() == name).cloned()
+    }
+
+    pub(crate) fn make_var(&self) -> &'static str {
+        match self {
+            Precision::Single => "BUILD_SINGLE",
+            Precision::Double => "BUILD_DOUBLE",
+            Precision::Complex => "BUILD_COMPLEX",
+            Precision::Complex16 => "BUILD_COMPLEX16",
+        }
+    }
+
+    /// Prefix of routines, e.g. `z` for `zgemm_`
+    fn prefix(&self) -> char {
+        match self {
+            Precision::Single => 's',
+            Precision::Double => 'd',
+            Precision::Complex => 'c',
+            Precision::Complex16 => 'z',
+        }
+    }
+}
+
+/// Goal of `make` for the OpenBLAS source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MakeGoal {
+    /// BLAS and CBLAS (`libs`)
+    Libs,
+    /// LAPACK and LAPACKE (`netlib`)
+    Netlib,
+    /// Shared library (`shared`)
+    Shared,
+    /// Build and run the BLAS tests on the build machine (`tests`)
+    Tests,
+}
+
+impl MakeGoal {
+    /// Goals to build the libraries
+    pub const DEFAULT: [MakeGoal; 3] = [MakeGoal::Libs, MakeGoal::Netlib, MakeGoal::Shared];
+
+    /// Name of the goal, e.g. `netlib`
+    pub fn name(&self) -> &'static str {
+        match self {
+            MakeGoal::Libs => "libs",
+            MakeGoal::Netlib => "netlib",
+            MakeGoal::Shared => "shared",
+            MakeGoal::Tests => "tests",
+        }
+    }
+
+    /// Parse the name of the goal
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "libs" => Some(MakeGoal::Libs),
+            "netlib" => Some(MakeGoal::Netlib),
+            "shared" => Some(MakeGoal::Shared),
+            "tests" => Some(MakeGoal::Tests),
+            _ => None,
+        }
+    }
 }
 
 /// make option generator
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///
+/// New options will be added to this struct as OpenBLAS grows,
+/// so it cannot be constructed by a struct expression outside of this crate.
+/// Use [Configure::builder] or [Configure::default] instead.
+///
+/// In TOML, options missing are same as [Configure::default], and unknown options are rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
 pub struct Configure {
     pub no_static: bool,
     pub no_shared: bool,
     pub no_cblas: bool,
     pub no_lapack: bool,
     pub no_lapacke: bool,
-    pub use_thread: bool,
+    /// Precisions to be built. All precisions are built if empty.
+    pub precisions: Vec<Precision>,
+    /// Compile with `-ffunction-sections -fdata-sections`,
+    /// so that the linker can drop unused kernels by `--gc-sections` when linked statically
+    pub gc_sections: bool,
+    /// Implementation of LAPACK, meaningless if `no_lapack`
+    pub lapack_backend: LapackBackend,
+    /// Path of an external LAPACK library, e.g. `/path/to/liblapack.a`,
+    /// linked with OpenBLAS built without LAPACK. Requires `no_lapack`.
+    pub external_lapack: Option<PathBuf>,
+    /// Build the threaded (`Some(true)`, `USE_THREAD=1`) or single-threaded (`Some(false)`, `USE_THREAD=0`) library.
+    /// If None, OpenBLAS builds the threaded one when the build machine has multiple cores,
+    /// except with `use_locking`, see [Configure::threaded].
+    pub use_thread: Option<bool>,
     pub use_openmp: bool,
+    /// Maximum number of threads at compile time (`NUM_THREADS`). OpenBLAS uses the number of cores of the build machine if None.
+    pub num_threads: Option<usize>,
+    /// Maximum number of concurrent calls into threaded OpenBLAS from different threads (`NUM_PARALLEL`)
+    pub num_parallel: Option<usize>,
+    /// Make single-threaded OpenBLAS thread-safe (`USE_LOCKING`)
+    pub use_locking: bool,
+    /// Disable (`Some(true)`) or enable (`Some(false)`) binding threads to CPU cores (`NO_AFFINITY`).
+    /// OpenBLAS disables it by default.
+    pub no_affinity: Option<bool>,
+    /// Use thread-local storage for the memory buffer instead of the central one (`USE_TLS`)
+    pub use_tls: bool,
+    /// Do not warm up the memory buffer at start of threads (`NO_WARMUP`)
+    pub no_warmup: bool,
     pub dynamic_arch: bool,
+    /// Targets whose kernels are built into a `dynamic_arch` library (`DYNAMIC_LIST`).
+    /// All targets supported by OpenBLAS are built if empty.
+    pub dynamic_list: Vec<Target>,
+    /// Add kernels for older CPUs into a `dynamic_arch` library (`DYNAMIC_OLDER`)
+    pub dynamic_older: bool,
     pub interface: Interface,
     pub target: Option<Target>,
+    /// C compiler (`CC`)
+    pub cc: Option<String>,
+    /// Fortran compiler (`FC`)
+    pub fc: Option<String>,
+    /// C compiler family, ignored if `cc` is specified
+    pub c_compiler: Option<CCompiler>,
+    /// Fortran compiler family, ignored if `fc` is specified
+    pub f_compiler: Option<FortranCompiler>,
+    /// Archiver (`AR`)
+    pub ar: Option<String>,
+    /// Additional flags for the C compiler (`CFLAGS`)
+    pub cflags: Vec<String>,
+    /// Additional flags for both C and Fortran compilers (`COMMON_OPT`), appended to `-O2`
+    pub common_opt: Vec<String>,
+    /// Additional flags for the Fortran compiler (`FCOMMON_OPT`)
+    pub fcommon_opt: Vec<String>,
+    /// Additional flags for the linker (`LDFLAGS`)
+    pub ldflags: Vec<String>,
+    /// C compiler for the host to build helper executables, e.g. `getarch` (`HOSTCC`)
+    pub hostcc: Option<String>,
+    /// Word size of the library. Determined by OpenBLAS if None.
+    pub binary: Option<Binary>,
+    /// Prefix of cross binutils, e.g. `aarch64-linux-gnu-` (`CROSS_SUFFIX`).
+    /// Also used to inspect the libraries of a foreign architecture.
+    pub cross_suffix: Option<String>,
+    /// Passed to the C and Fortran compilers as `--sysroot`
+    pub sysroot: Option<PathBuf>,
+    /// Performance tuning options
+    pub tuning: Tuning,
+    /// Debug and instrumentation options
+    pub mode: BuildMode,
+    /// Prefix added to all exported symbols, e.g. `oblas_` for `oblas_dgemm_` (`SYMBOLPREFIX`)
+    pub symbol_prefix: Option<String>,
+    /// Suffix added to all exported symbols (`SYMBOLSUFFIX`)
+    pub symbol_suffix: Option<String>,
+    /// Suffix of the library name, e.g. `libopenblas_64.a` for `64` (`LIBNAMESUFFIX`)
+    pub libname_suffix: Option<String>,
+    /// Additional arguments to `make`, e.g. `["NUM_THREADS=8"]`, appended after those generated from other options
+    pub extra_args: Vec<String>,
+    /// Goals of `make`, [MakeGoal::DEFAULT] by default.
+    /// Goals only add to the libraries, and are not a part of the build record and the cache key.
+    pub goals: Vec<MakeGoal>,
+    /// Install the libraries and headers by `make install PREFIX=...` after build,
+    /// e.g. `include/cblas.h` and `lib/libopenblas.a` under the prefix.
+    /// Not a part of the build record and the cache key, same as `goals`.
+    pub install_prefix: Option<PathBuf>,
+    /// Number of parallel jobs of `make`, which overrides the jobserver of cargo and `NUM_JOBS`.
+    /// This is not a part of the build record and the cache key since it does not change the deliverables.
+    pub jobs: Option<usize>,
 }
 
 impl Default for Configure {
@@ -139,26 +342,290 @@ impl Default for Configure {
             no_cblas: false,
             no_lapack: false,
             no_lapacke: false,
-            use_thread: false,
+            precisions: Vec::new(),
+            gc_sections: false,
+            lapack_backend: LapackBackend::Fortran,
+            external_lapack: None,
+            use_thread: None,
             use_openmp: false,
+            num_threads: None,
+            num_parallel: None,
+            use_locking: false,
+            no_affinity: None,
+            use_tls: false,
+            no_warmup: false,
             dynamic_arch: false,
+            dynamic_list: Vec::new(),
+            dynamic_older: false,
             interface: Interface::LP64,
             target: None,
+            cc: None,
+            fc: None,
+            c_compiler: None,
+            f_compiler: None,
+            ar: None,
+            cflags: Vec::new(),
+            common_opt: Vec::new(),
+            fcommon_opt: Vec::new(),
+            ldflags: Vec::new(),
+            hostcc: None,
+            binary: None,
+            cross_suffix: None,
+            sysroot: None,
+            tuning: Tuning::default(),
+            mode: BuildMode::default(),
+            symbol_prefix: None,
+            symbol_suffix: None,
+            libname_suffix: None,
+            extra_args: Vec::new(),
+            goals: MakeGoal::DEFAULT.to_vec(),
+            install_prefix: None,
+            jobs: None,
         }
     }
 }
 
+/// Compilers used for the build, and their versions
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Compilers {
+    /// C compiler command
+    pub cc: String,
+    /// First line of `cc --version`, None if `cc` cannot be executed
+    pub cc_version: Option<String>,
+    /// Fortran compiler command, None if OpenBLAS does not use Fortran
+    pub fc: Option<String>,
+    /// First line of `fc --version`
+    pub fc_version: Option<String>,
+}
+
+impl Compilers {
+    fn new(cfg: &Configure, make_conf: &MakeConf) -> Self {
+        let cc = cfg.cc_command().unwrap_or_else(|| "cc".into());
+        let fc = cfg
+            .fc_command()
+            .or_else(|| Some(make_conf.fc.clone()))
+            .filter(|fc| !make_conf.no_fortran && !fc.is_empty());
+        Compilers {
+            cc_version: compiler_version(&cc),
+            fc_version: fc.as_ref().and_then(|fc| compiler_version(fc)),
+            cc,
+            fc,
+        }
+    }
+}
+
+/// First line of `[compiler] --version`
+///
+/// `compiler` may have arguments, e.g. `clang --target=aarch64-linux-gnu`
+pub(crate) fn compiler_version(compiler: &str) -> Option<String> {
+    let mut words = compiler.split_whitespace();
+    let out = Command::new(words.next()?)
+        .args(words)
+        .arg("--version")
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .find(|line| !line.is_empty())
+}
+
+/// Read `VERSION` in `Makefile.rule` of OpenBLAS source
+pub(crate) fn openblas_version(root: &Path) -> Option<String> {
+    let rule = fs::read_to_string(root.join("Makefile.rule")).ok()?;
+    rule.lines().find_map(|line| {
+        let mut entry = line.splitn(2, '=');
+        if entry.next()?.trim() == "VERSION" {
+            Some(entry.next()?.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
 /// Deliverables of `make` command
+#[derive(Debug, Clone, Serialize)]
 pub struct Deliverables {
     /// None if `no_static`
     pub static_lib: Option<LibInspect>,
     /// None if `no_shared`
     pub shared_lib: Option<LibInspect>,
+    /// External LAPACK library specified by `external_lapack`,
+    /// which should be linked before OpenBLAS to resolve its BLAS calls
+    pub external_lapack: Option<LibInspect>,
     /// Inspection what `make` command really show.
     pub make_conf: MakeConf,
+    pub compilers: Compilers,
+    /// Version of OpenBLAS, e.g. `0.3.10`
+    pub openblas_version: Option<String>,
+    /// Tuning options which the library is built with
+    pub tuning: Tuning,
+    /// Directory of the libraries, i.e. `lib` under `install_prefix`, or `out_dir` if not installed
+    pub lib_dir: PathBuf,
+    /// Directory of the headers, i.e. `include` under `install_prefix`. None if not installed.
+    pub include_dir: Option<PathBuf>,
 }
 
 impl Configure {
+    /// Start building a configuration, see [ConfigureBuilder]
+    pub fn builder() -> ConfigureBuilder {
+        ConfigureBuilder::default()
+    }
+
+    /// Whether the library is threaded, i.e. `use_thread` or `Some(false)` with `use_locking`
+    ///
+    /// None if OpenBLAS decides it by the number of cores of the build machine.
+    pub fn threaded(&self) -> Option<bool> {
+        self.use_thread
+            .or(if self.use_locking { Some(false) } else { None })
+    }
+
+    /// Check contradictory or meaningless combination of options
+    pub fn validate(&self) -> Result<(), ConfigureError> {
+        if self.no_static && self.no_shared {
+            return Err(ConfigureError::NoLibrary);
+        }
+        let use_thread = self.use_thread == Some(true);
+        if self.use_openmp && !use_thread {
+            return Err(ConfigureError::OpenMPWithoutThread);
+        }
+        if self.num_threads == Some(0) {
+            return Err(ConfigureError::ZeroThreads {
+                option: "num_threads",
+            });
+        }
+        if self.num_parallel == Some(0) {
+            return Err(ConfigureError::ZeroThreads {
+                option: "num_parallel",
+            });
+        }
+        if self.jobs == Some(0) {
+            return Err(ConfigureError::ZeroThreads { option: "jobs" });
+        }
+        self.tuning.validate(use_thread)?;
+        self.mode.validate()?;
+        if use_thread {
+            // Threaded OpenBLAS is always thread-safe
+            if self.use_locking {
+                return Err(ConfigureError::LockingWithThread);
+            }
+        } else if self.num_parallel.is_some() {
+            return Err(ConfigureError::RequiresThread {
+                option: "num_parallel",
+            });
+        }
+        if self.interface == Interface::ILP64 && self.binary == Some(Binary::B32) {
+            return Err(ConfigureError::Ilp64On32Bit);
+        }
+        if self.no_lapack && !self.no_lapacke {
+            return Err(ConfigureError::LapackeWithoutLapack);
+        }
+        if self.no_lapack && self.lapack_backend != LapackBackend::Fortran {
+            return Err(ConfigureError::LapackBackendWithoutLapack);
+        }
+        if self.external_lapack.is_some() && !self.no_lapack {
+            return Err(ConfigureError::ExternalLapackWithLapack);
+        }
+        // External LAPACK calls BLAS by the original names
+        if self.external_lapack.is_some()
+            && (self.symbol_prefix.is_some() || self.symbol_suffix.is_some())
+        {
+            return Err(ConfigureError::ExternalLapackWithSymbolAffix);
+        }
+        let required = [
+            (MakeGoal::Libs, true),
+            (MakeGoal::Netlib, !self.no_lapack),
+            (MakeGoal::Shared, !self.no_shared),
+        ];
+        for (goal, required) in &required {
+            if *required && !self.goals.contains(goal) {
+                return Err(ConfigureError::MissingMakeGoal { goal: goal.name() });
+            }
+        }
+        if !self.dynamic_arch {
+            if !self.dynamic_list.is_empty() {
+                return Err(ConfigureError::RequiresDynamicArch {
+                    option: "dynamic_list",
+                });
+            }
+            if self.dynamic_older {
+                return Err(ConfigureError::RequiresDynamicArch {
+                    option: "dynamic_older",
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Precisions expected in the library, i.e. `precisions` with those required by them
+    pub fn built_precisions(&self) -> Vec<Precision> {
+        if self.precisions.is_empty() {
+            return Precision::ALL.to_vec();
+        }
+        Precision::ALL
+            .iter()
+            .filter(|p| {
+                self.precisions.contains(p)
+                    || (**p == Precision::Single && self.precisions.contains(&Precision::Complex))
+                    || (**p == Precision::Double && self.precisions.contains(&Precision::Complex16))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Flags of `gc_sections`
+    fn section_flags(&self) -> &'static [&'static str] {
+        if self.gc_sections {
+            &["-ffunction-sections", "-fdata-sections"]
+        } else {
+            &[]
+        }
+    }
+
+    /// Name of the library to be linked, e.g. `openblas_64` for `-lopenblas_64`
+    pub fn lib_name(&self) -> String {
+        match &self.libname_suffix {
+            Some(suffix) => format!("openblas_{}", suffix),
+            None => "openblas".into(),
+        }
+    }
+
+    /// ILP64 variant of this configuration, which can be linked with this LP64 library into one binary
+    ///
+    /// Symbols are suffixed by `64_`, e.g. `dgemm_64_`, and the library is named `libopenblas_64`.
+    ///
+    /// ```
+    /// use openblas_build::*;
+    /// let cfg = Configure::default().ilp64_variant();
+    /// assert_eq!(cfg.interface, Interface::ILP64);
+    /// assert_eq!(cfg.symbol_suffix.as_deref(), Some("64_"));
+    /// assert_eq!(cfg.lib_name(), "openblas_64");
+    /// ```
+    pub fn ilp64_variant(&self) -> Configure {
+        let mut cfg = self.clone();
+        cfg.interface = Interface::ILP64;
+        cfg.symbol_suffix = Some("64_".into());
+        cfg.libname_suffix = Some("64".into());
+        cfg
+    }
+
+    /// C compiler command specified by `cc` or `c_compiler`
+    pub(crate) fn cc_command(&self) -> Option<String> {
+        self.cc
+            .clone()
+            .or_else(|| self.c_compiler.map(|c| c.command().into()))
+    }
+
+    /// Fortran compiler command specified by `fc` or `f_compiler`
+    pub(crate) fn fc_command(&self) -> Option<String> {
+        self.fc
+            .clone()
+            .or_else(|| self.f_compiler.map(|f| f.command().into()))
+    }
+
     fn make_args(&self) -> Vec<String> {
         let mut args = Vec::new();
         if self.no_static {
@@ -176,62 +643,359 @@ impl Configure {
         if self.no_lapacke {
             args.push("NO_LAPACKE=1".into())
         }
-        if self.use_thread {
-            args.push("USE_THREAD=1".into())
+        for precision in &self.precisions {
+            args.push(format!("{}=1", precision.make_var()))
+        }
+        match self.lapack_backend {
+            LapackBackend::Fortran => {}
+            LapackBackend::C => {
+                args.push("C_LAPACK=1".into());
+                args.push("NOFORTRAN=1".into());
+            }
+            LapackBackend::ReLapack => args.push("BUILD_RELAPACK=1".into()),
+        }
+        match self.threaded() {
+            Some(true) => args.push("USE_THREAD=1".into()),
+            // OpenBLAS builds the threaded library unless `USE_THREAD=0` explicitly
+            Some(false) => args.push("USE_THREAD=0".into()),
+            None => {}
         }
         if self.use_openmp {
             args.push("USE_OPENMP=1".into())
         }
+        if let Some(num_threads) = self.num_threads {
+            args.push(format!("NUM_THREADS={}", num_threads))
+        }
+        if let Some(num_parallel) = self.num_parallel {
+            args.push(format!("NUM_PARALLEL={}", num_parallel))
+        }
+        if self.use_locking {
+            args.push("USE_LOCKING=1".into())
+        }
+        if let Some(no_affinity) = self.no_affinity {
+            args.push(format!("NO_AFFINITY={}", no_affinity as i32))
+        }
+        args.extend(self.tuning.make_args());
+        args.extend(self.mode.make_args());
+        if self.use_tls {
+            args.push("USE_TLS=1".into())
+        }
+        if self.no_warmup {
+            args.push("NO_WARMUP=1".into())
+        }
+        if self.dynamic_arch {
+            args.push("DYNAMIC_ARCH=1".into());
+            if !self.dynamic_list.is_empty() {
+                let list: Vec<_> = self
+                    .dynamic_list
+                    .iter()
+                    .map(|target| target.to_string())
+                    .collect();
+                args.push(format!("DYNAMIC_LIST={}", list.join(" ")));
+            }
+            if self.dynamic_older {
+                args.push("DYNAMIC_OLDER=1".into())
+            }
+        }
         if matches!(self.interface, Interface::ILP64) {
             args.push("INTERFACE64=1".into())
         }
         if let Some(target) = self.target.as_ref() {
-            args.push(format!("TARGET={:?}", target))
+            args.push(format!("TARGET={}", target))
+        }
+        if let Some(binary) = self.binary {
+            args.push(match binary {
+                Binary::B32 => "BINARY=32".into(),
+                Binary::B64 => "BINARY=64".into(),
+            })
+        }
+        // OpenBLAS has no option for sysroot, and it is passed with compilers
+        let sysroot = self
+            .sysroot
+            .as_ref()
+            .map(|sysroot| format!(" --sysroot={}", sysroot.display()))
+            .unwrap_or_default();
+        let cc = self.cc_command();
+        if cc.is_some() || self.sysroot.is_some() {
+            let cc = cc.as_deref().unwrap_or("cc");
+            args.push(format!("CC={}{}", cc, sysroot))
+        }
+        if let Some(fc) = self.fc_command() {
+            args.push(format!("FC={}{}", fc, sysroot))
         }
+        if let Some(ar) = self.ar.as_ref() {
+            args.push(format!("AR={}", ar))
+        }
+        // OpenBLAS appends its own flags by `override CFLAGS +=`
+        let cflags: Vec<&str> = self
+            .cflags
+            .iter()
+            .map(String::as_str)
+            .chain(self.section_flags().iter().cloned())
+            .collect();
+        if !cflags.is_empty() {
+            args.push(format!("CFLAGS={}", cflags.join(" ")))
+        }
+        if let Some(hostcc) = self.hostcc.as_ref() {
+            args.push(format!("HOSTCC={}", hostcc))
+        }
+        if let Some(cross_suffix) = self.cross_suffix.as_ref() {
+            args.push(format!("CROSS_SUFFIX={}", cross_suffix))
+        }
+        if let Some(prefix) = self.symbol_prefix.as_ref() {
+            args.push(format!("SYMBOLPREFIX={}", prefix))
+        }
+        if let Some(suffix) = self.symbol_suffix.as_ref() {
+            args.push(format!("SYMBOLSUFFIX={}", suffix))
+        }
+        if let Some(suffix) = self.libname_suffix.as_ref() {
+            args.push(format!("LIBNAMESUFFIX={}", suffix))
+        }
+        args.extend(self.extra_args.iter().cloned());
         args
     }
 
+    /// Environment variables for `make`
+    ///
+    /// These flags are passed as environment variables instead of arguments,
+    /// since arguments override the flags appended in the OpenBLAS Makefiles, e.g. `FCOMMON_OPT += -frecursive`.
+    fn make_env(&self) -> Vec<(&'static str, String)> {
+        let mut envs = Vec::new();
+        let common_opt: Vec<&str> = self
+            .common_opt
+            .iter()
+            .map(String::as_str)
+            .chain(self.mode.flags())
+            .collect();
+        if !common_opt.is_empty() {
+            // `-O2` is the default of OpenBLAS if `COMMON_OPT` is not set,
+            // and `DEBUG=1` appends `-g` instead
+            let opt = if self.mode.debug { "" } else { "-O2 " };
+            envs.push(("COMMON_OPT", format!("{}{}", opt, common_opt.join(" "))));
+        }
+        let fcommon_opt: Vec<&str> = self
+            .fcommon_opt
+            .iter()
+            .map(String::as_str)
+            .chain(self.section_flags().iter().cloned())
+            .collect();
+        if !fcommon_opt.is_empty() {
+            envs.push(("FCOMMON_OPT", fcommon_opt.join(" ")));
+        }
+        if !self.ldflags.is_empty() {
+            envs.push(("LDFLAGS", self.ldflags.join(" ")));
+        }
+        envs
+    }
+
+    /// Parallelism of `make` as arguments and environment variables
+    ///
+    /// OpenBLAS runs sub-makes with `-j` of the number of cores by default,
+    /// which oversubscribes the machine when cargo builds other crates in parallel.
+    /// The jobs are limited in the following priority:
+    ///
+    /// 1. `jobs` of `self`
+    /// 2. jobserver of cargo, i.e. `cargo_makeflags` from `CARGO_MAKEFLAGS`
+    /// 3. `num_jobs` from `NUM_JOBS` set by cargo
+    ///
+    /// and `MAKE_NB_JOBS=0` lets the sub-makes share them instead of their own `-j`.
+    fn make_jobs(
+        &self,
+        cargo_makeflags: Option<&str>,
+        num_jobs: Option<&str>,
+    ) -> (Vec<String>, Vec<(&'static str, String)>) {
+        let shared = || "MAKE_NB_JOBS=0".to_string();
+        if let Some(jobs) = self.jobs {
+            return (vec![format!("-j{}", jobs), shared()], Vec::new());
+        }
+        if let Some(makeflags) = cargo_makeflags.filter(|flags| !flags.trim().is_empty()) {
+            return (vec![shared()], vec![("MAKEFLAGS", makeflags.to_string())]);
+        }
+        match num_jobs.and_then(|jobs| jobs.trim().parse::<usize>().ok()) {
+            Some(jobs) if jobs > 0 => (vec![format!("-j{}", jobs), shared()], Vec::new()),
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+
+    /// `make` in `out_dir` with the arguments and environment variables of `self`
+    ///
+    /// - cargo sets `TARGET` environment variable as target triple (e.g. x86_64-unknown-linux-gnu)
+    ///   while binding build.rs, but `make` read it as CPU target specification.
+    /// - `CC`, `AR` and `CFLAGS` in the environment are not used implicitly.
+    ///   They are passed explicitly from `self`, see [Configure::fill_cc_env].
+    /// - `MAKEFLAGS` in the environment, e.g. of an outer `make`, is not inherited.
+    fn make_command(&self, out_dir: &Path) -> Command {
+        let mut make = Command::new("make");
+        make.current_dir(out_dir)
+            .args(self.make_args())
+            .env_remove("TARGET")
+            .env_remove("MAKEFLAGS")
+            .env_remove("CC")
+            .env_remove("AR")
+            .env_remove("CFLAGS")
+            .env_remove("COMMON_OPT")
+            .env_remove("FCOMMON_OPT")
+            .env_remove("LDFLAGS")
+            .envs(self.make_env());
+        make
+    }
+
+    /// Check `target` and `dynamic_list` can be built for Rust's `target_arch`, e.g. `"x86_64"`
+    pub fn validate_target_arch(&self, target_arch: &str) -> Result<(), Error> {
+        for target in self.target.iter().chain(self.dynamic_list.iter()) {
+            if !target.is_valid_for(target_arch) {
+                return Err(Error::TargetArchMismatch {
+                    target: *target,
+                    target_arch: target_arch.into(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Inspect `external_lapack`, and check it provides LAPACK symbols
+    fn inspect_external_lapack(&self) -> Result<Option<LibInspect>, Error> {
+        let path = match &self.external_lapack {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let lib = LibInspect::with_binutils(path, self.cross_suffix.as_deref().unwrap_or(""))?;
+        if !lib.has_lapack() {
+            return Err(Error::ExternalLapackInvalid { path: path.clone() });
+        }
+        Ok(Some(lib))
+    }
+
     /// Inspect existing build deliverables, and validate them.
     ///
     /// Error
     /// ------
     /// - No build deliverables exist
+    /// - Build record written by [Configure::build] does not exist, or differs from `self`
+    ///   ([Error::ConfigureMismatch] lists the differing options)
     /// - Build deliverables are not valid
     ///   - e.g. `self.no_lapack == false`, but the existing library does not contains LAPACK symbols.
+    ///     This is [Error::FortranCompilerNotFound] if OpenBLAS skipped the Fortran LAPACK due to the absence of Fortran compiler.
+    ///   - e.g. `self.dynamic_arch == true`, but kernels of a target in `self.dynamic_list` are not found.
+    ///   - e.g. `self.precisions == [Double]`, but the library contains `sgemm_` or does not contain `dgemm_`.
+    /// - `self.external_lapack` does not exist or does not contain LAPACK symbols
+    /// - Libraries or headers are not installed under `self.install_prefix`
     ///
     pub fn inspect(&self, out_dir: impl AsRef<Path>) -> Result<Deliverables, Error> {
         let out_dir = out_dir.as_ref();
         let make_conf = MakeConf::new(out_dir.join("Makefile.conf"))?;
+        self.check_record(out_dir)?;
 
-        if !self.no_lapack && make_conf.no_fortran {
-            return Err(Error::FortranCompilerNotFound);
+        // Use the binutils for the target if exists since the libraries may be of a foreign architecture
+        let binutils_prefix = self.cross_suffix.as_deref().unwrap_or("");
+        let inspect_lib = |path: PathBuf| -> Result<LibInspect, Error> {
+            Ok(
+                LibInspect::with_binutils(path, binutils_prefix)?.with_symbol_affix(
+                    self.symbol_prefix.as_deref().unwrap_or(""),
+                    self.symbol_suffix.as_deref().unwrap_or(""),
+                ),
+            )
+        };
+        let (lib_dir, include_dir) = match &self.install_prefix {
+            Some(prefix) => (prefix.join("lib"), Some(prefix.join("include"))),
+            None => (out_dir.to_path_buf(), None),
+        };
+        if let Some(include_dir) = &include_dir {
+            let mut installed = vec![include_dir.join("openblas_config.h")];
+            if !self.no_static {
+                installed.push(lib_dir.join(format!("lib{}.a", self.lib_name())));
+            }
+            if !self.no_shared {
+                installed.push(lib_dir.join(format!("lib{}.so", self.lib_name())));
+            }
+            if let Some(path) = installed.into_iter().find(|path| !path.exists()) {
+                return Err(Error::InstalledFileNotExist { path });
+            }
         }
-
-        Ok(Deliverables {
+        let deliv = Deliverables {
             static_lib: if !self.no_static {
-                Some(LibInspect::new(out_dir.join("libopenblas.a"))?)
+                Some(inspect_lib(
+                    out_dir.join(format!("lib{}.a", self.lib_name())),
+                )?)
             } else {
                 None
             },
             shared_lib: if !self.no_shared {
-                Some(LibInspect::new(if cfg!(target_os = "macos") {
-                    out_dir.join("libopenblas.dylib")
+                Some(inspect_lib(if cfg!(target_os = "macos") {
+                    out_dir.join(format!("lib{}.dylib", self.lib_name()))
                 } else {
-                    out_dir.join("libopenblas.so")
+                    out_dir.join(format!("lib{}.so", self.lib_name()))
                 })?)
             } else {
                 None
             },
+            external_lapack: self.inspect_external_lapack()?,
+            compilers: Compilers::new(self, &make_conf),
+            openblas_version: openblas_version(out_dir),
+            tuning: self.tuning.clone(),
+            lib_dir,
+            include_dir,
             make_conf,
-        })
+        };
+
+        let lib = deliv
+            .static_lib
+            .as_ref()
+            .or(deliv.shared_lib.as_ref())
+            .expect("Either static or shared library must be built");
+
+        let built = self.built_precisions();
+        for precision in &Precision::ALL {
+            let expected = built.contains(precision);
+            if lib.has_function(&format!("{}gemm_", precision.prefix())) != expected {
+                return Err(Error::PrecisionMismatch {
+                    precision: *precision,
+                    expected,
+                });
+            }
+        }
+
+        if !self.no_lapack {
+            if !lib.has_lapack() {
+                if deliv.make_conf.no_fortran && self.lapack_backend != LapackBackend::C {
+                    return Err(Error::FortranCompilerNotFound);
+                }
+                return Err(Error::LapackNotFound);
+            }
+            if self.lapack_backend == LapackBackend::ReLapack && !lib.has_relapack() {
+                return Err(Error::RelapackNotBuilt);
+            }
+        }
+
+        if self.dynamic_arch {
+            if !lib.has_dynamic_arch() {
+                return Err(Error::DynamicArchNotBuilt);
+            }
+            for target in &self.dynamic_list {
+                if !lib.has_kernels_for(target) {
+                    return Err(Error::DynamicKernelNotFound { target: *target });
+                }
+            }
+        }
+
+        Ok(deliv)
     }
 
     /// Build OpenBLAS
     ///
-    /// Libraries are created directly under `out_dir` e.g. `out_dir/libopenblas.a`
+    /// Libraries are created directly under `out_dir` e.g. `out_dir/libopenblas.a`,
+    /// or `out_dir/libopenblas_64.a` for `libname_suffix = Some("64")`.
+    /// Sources in `openblas_root` are staged into `out_dir`,
+    /// and OpenBLAS is rebuilt if some of them have changed since the previous build.
+    /// Everything in `out_dir` is removed to rebuild, as well as when the options have changed.
     ///
     /// Error
     /// -----
+    /// - Options are contradictory, see [Configure::validate]
+    /// - `target` or `dynamic_list` cannot be built for `CARGO_CFG_TARGET_ARCH`
+    ///   (or the host architecture if it is not set, i.e. not called from build.rs)
+    /// - [Target::Custom] in `target` or `dynamic_list` is not listed in `TargetList.txt` of `openblas_root`
+    /// - `external_lapack` does not provide LAPACK
     /// - Build deliverables are invalid same as [inspect].
     ///   This means that the system environment is not appropriate to execute `make`,
     ///   e.g. LAPACK is required but there is no Fortran compiler.
@@ -241,56 +1005,64 @@ impl Configure {
         openblas_root: impl AsRef<Path>,
         out_dir: impl AsRef<Path>,
     ) -> Result<Deliverables, Error> {
+        self.validate()?;
+        let target_arch =
+            env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_else(|_| env::consts::ARCH.into());
+        self.validate_target_arch(&target_arch)?;
+        // Check before long `make`
+        self.inspect_external_lapack()?;
+
+        let root = openblas_root.as_ref();
+        for target in self.target.iter().chain(self.dynamic_list.iter()) {
+            if let Target::Custom(name) = target {
+                if !TargetList::new(root)?.contains(name) {
+                    return Err(Error::UnknownTarget {
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+
         let out_dir = out_dir.as_ref();
         if !out_dir.exists() {
             fs::create_dir_all(out_dir)?;
         }
 
+        // Stage OpenBLAS sources from this crate to `out_dir`, see [stage_source].
+        // Objects of the existing build must not be reused if some sources are replaced.
+        let replaced = stage_source(root, out_dir, self.goals.contains(&MakeGoal::Tests))?;
+
         // Do not build if libraries and Makefile.conf already exist and are valid
-        if let Ok(deliv) = self.inspect(out_dir) {
-            return Ok(deliv);
-        }
+        let stale = match self.inspect(out_dir) {
+            Ok(deliv) if replaced == 0 => return Ok(deliv),
+            Ok(_) => true,
+            Err(e) => replaced > 0 || is_stale(&e),
+        };
 
-        // Copy OpenBLAS sources from this crate to `out_dir`
-        let root = openblas_root.as_ref();
-        for entry in WalkDir::new(&root) {
-            let entry = entry.expect("Unknown IO error while walkdir");
-            let dest = out_dir.join(
-                entry
-                    .path()
-                    .strip_prefix(&root)
-                    .expect("Directory entry is not under root"),
-            );
-            if dest.exists() {
-                // Do not overwrite
-                // Cache of previous build should be cleaned by `cargo clean`
-                continue;
-            }
-            if entry.file_type().is_dir() {
-                fs::create_dir(&dest)?;
-            }
-            if entry.file_type().is_file() {
-                fs::copy(entry.path(), &dest)?;
-            }
+        // Objects of the existing build with another configuration must not be reused
+        if stale {
+            restage_source(root, out_dir, self.goals.contains(&MakeGoal::Tests))?;
         }
 
         // Run `make` as an subprocess
         //
-        // - This will automatically run in parallel without `-j` flag
+        // - This runs in parallel within the jobs given by cargo, see [Configure::make_jobs].
         // - The `make` of OpenBLAS outputs 30k lines,
         //   which will be redirected into `out.log` and `err.log`.
-        // - cargo sets `TARGET` environment variable as target triple (e.g. x86_64-unknown-linux-gnu)
-        //   while binding build.rs, but `make` read it as CPU target specification.
-        //
+
+        let (jobs_args, jobs_env) = self.make_jobs(
+            env::var("CARGO_MAKEFLAGS").ok().as_deref(),
+            env::var("NUM_JOBS").ok().as_deref(),
+        );
         let out = fs::File::create(out_dir.join("out.log")).expect("Cannot create log file");
         let err = fs::File::create(out_dir.join("err.log")).expect("Cannot create log file");
-        match Command::new("make")
-            .current_dir(out_dir)
+        match self
+            .make_command(out_dir)
             .stdout(unsafe { Stdio::from_raw_fd(out.into_raw_fd()) }) // this works only for unix
             .stderr(unsafe { Stdio::from_raw_fd(err.into_raw_fd()) })
-            .args(&self.make_args())
-            .args(&["libs", "netlib", "shared"])
-            .env_remove("TARGET")
+            .args(jobs_args)
+            .args(self.goals.iter().map(MakeGoal::name))
+            .envs(jobs_env)
             .check_call()
         {
             Ok(_) => {}
@@ -306,13 +1078,71 @@ impl Configure {
             }
         }
 
+        // `make install` needs the same options as the build, e.g. `LIBNAMESUFFIX`
+        if let Some(prefix) = &self.install_prefix {
+            let log =
+                fs::File::create(out_dir.join("install.log")).expect("Cannot create log file");
+            let result = self
+                .make_command(out_dir)
+                .stdout(log.try_clone()?)
+                .stderr(log)
+                .arg("install")
+                .arg(format!("PREFIX={}", prefix.display()))
+                .check_call();
+            if let Err(err @ Error::NonZeroExitStatus { .. }) = result {
+                eprintln!(
+                    "{}",
+                    fs::read_to_string(out_dir.join("install.log")).expect("Cannot read log file")
+                );
+                return Err(err);
+            }
+            result?;
+        }
+
+        self.write_record(out_dir)?;
         self.inspect(out_dir)
     }
 }
 
+/// Whether the existing build failed to [Configure::inspect] with `error` must be cleaned
+///
+/// Only missing deliverables, e.g. of an interrupted build, are completed by `make` as is.
+/// Other errors, e.g. a different or corrupted build record, make the existing build stale.
+fn is_stale(error: &Error) -> bool {
+    !matches!(
+        error,
+        Error::MakeConfNotExist { .. }
+            | Error::LibraryNotExist { .. }
+            | Error::InstalledFileNotExist { .. }
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::manifest::BUILD_RECORD;
+
+    #[test]
+    fn stale() {
+        let out_dir =
+            std::env::temp_dir().join(format!("openblas-build-stale-{}", std::process::id()));
+        fs::create_dir_all(&out_dir).unwrap();
+        let cfg = Configure::default();
+        // Nothing is built yet
+        assert!(!is_stale(&cfg.inspect(&out_dir).unwrap_err()));
+
+        fs::write(out_dir.join("Makefile.conf"), "OSNAME=Linux\n").unwrap();
+        assert!(is_stale(&cfg.inspect(&out_dir).unwrap_err()));
+        cfg.write_record(&out_dir).unwrap();
+        // Interrupted before the libraries are built
+        assert!(!is_stale(&cfg.inspect(&out_dir).unwrap_err()));
+
+        fs::write(out_dir.join(BUILD_RECORD), "no_static = [").unwrap();
+        let err = cfg.inspect(&out_dir).unwrap_err();
+        assert!(matches!(err, Error::InvalidToml(_)));
+        assert!(is_stale(&err));
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
 
     #[ignore]
     #[test]
@@ -331,8 +1161,10 @@ mod tests {
     #[test]
     fn build_no_shared() {
         let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let mut opt = Configure::default();
-        opt.no_shared = true;
+        let opt = Configure {
+            no_shared: true,
+            ..Default::default()
+        };
         let detail = opt
             .build(
                 root.join("../openblas-src/source"),
@@ -346,8 +1178,10 @@ mod tests {
     #[test]
     fn build_no_lapacke() {
         let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let mut opt = Configure::default();
-        opt.no_lapacke = true;
+        let opt = Configure {
+            no_lapacke: true,
+            ..Default::default()
+        };
         let detail = opt
             .build(
                 root.join("../openblas-src/source"),
@@ -363,8 +1197,11 @@ mod tests {
     #[test]
     fn build_openmp() {
         let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let mut opt = Configure::default();
-        opt.use_openmp = true;
+        let opt = Configure {
+            use_thread: Some(true),
+            use_openmp: true,
+            ..Default::default()
+        };
         let detail = opt
             .build(
                 root.join("../openblas-src/source"),
@@ -373,4 +1210,261 @@ mod tests {
             .unwrap();
         assert!(detail.shared_lib.unwrap().has_lib("gomp"));
     }
+
+    #[test]
+    fn make_args_dynamic_arch() {
+        let mut opt = Configure {
+            dynamic_list: vec![Target::HASWELL, Target::SKYLAKEX],
+            dynamic_older: true,
+            ..Default::default()
+        };
+        // Dynamic options are meaningless without `dynamic_arch`
+        assert!(!opt.make_args().iter().any(|arg| arg.starts_with("DYNAMIC")));
+
+        opt.dynamic_arch = true;
+        let args = opt.make_args();
+        assert!(args.contains(&"DYNAMIC_ARCH=1".to_string()));
+        assert!(args.contains(&"DYNAMIC_LIST=HASWELL SKYLAKEX".to_string()));
+        assert!(args.contains(&"DYNAMIC_OLDER=1".to_string()));
+    }
+
+    #[test]
+    fn make_args_cross() {
+        let mut opt = Configure::default();
+        opt.fill_cross_defaults("aarch64-unknown-linux-gnu", "x86_64-unknown-linux-gnu");
+        opt.sysroot = Some("/usr/aarch64-linux-gnu".into());
+        let args = opt.make_args();
+        assert!(args.contains(&"BINARY=64".to_string()));
+        assert!(args.contains(&"TARGET=ARMV8".to_string()));
+        assert!(
+            args.contains(&"CC=aarch64-linux-gnu-gcc --sysroot=/usr/aarch64-linux-gnu".to_string())
+        );
+        assert!(args.contains(&"HOSTCC=cc".to_string()));
+        assert!(args.contains(&"CROSS_SUFFIX=aarch64-linux-gnu-".to_string()));
+    }
+
+    #[test]
+    fn make_compiler_flags() {
+        let opt = Configure::builder()
+            .c_compiler(CCompiler::Clang)
+            .f_compiler(FortranCompiler::Flang)
+            .common_opt(vec!["-march=armv8.2-a"])
+            .build()
+            .unwrap();
+        let args = opt.make_args();
+        assert!(args.contains(&"CC=clang".to_string()));
+        assert!(args.contains(&"FC=flang-new".to_string()));
+        assert_eq!(
+            opt.make_env(),
+            vec![("COMMON_OPT", "-O2 -march=armv8.2-a".to_string())]
+        );
+    }
+
+    #[test]
+    fn make_args_lapack_backend() {
+        let mut opt = Configure {
+            lapack_backend: LapackBackend::C,
+            ..Default::default()
+        };
+        let args = opt.make_args();
+        assert!(args.contains(&"C_LAPACK=1".to_string()));
+        assert!(args.contains(&"NOFORTRAN=1".to_string()));
+
+        opt.no_lapack = true;
+        opt.no_lapacke = true;
+        assert_eq!(
+            opt.validate(),
+            Err(ConfigureError::LapackBackendWithoutLapack)
+        );
+    }
+
+    #[test]
+    fn external_lapack() {
+        let builder = Configure::builder().external_lapack("/nonexistent/liblapack.a");
+        assert_eq!(
+            builder.clone().build(),
+            Err(ConfigureError::ExternalLapackWithLapack)
+        );
+        let opt = builder.no_lapack(true).no_lapacke(true).build().unwrap();
+        assert!(matches!(
+            opt.inspect_external_lapack(),
+            Err(Error::LibraryNotExist { .. })
+        ));
+    }
+
+    #[test]
+    fn precisions() {
+        let mut opt = Configure::default();
+        assert_eq!(opt.built_precisions(), Precision::ALL.to_vec());
+
+        opt.precisions = vec![Precision::Complex16];
+        opt.gc_sections = true;
+        assert_eq!(
+            opt.built_precisions(),
+            vec![Precision::Double, Precision::Complex16]
+        );
+        let args = opt.make_args();
+        assert!(args.contains(&"BUILD_COMPLEX16=1".to_string()));
+        assert!(args.contains(&"CFLAGS=-ffunction-sections -fdata-sections".to_string()));
+    }
+
+    #[test]
+    fn threading() {
+        let opt = Configure::builder()
+            .use_thread(true)
+            .num_threads(256)
+            .num_parallel(4)
+            .no_affinity(false)
+            .build()
+            .unwrap();
+        let args = opt.make_args();
+        assert!(args.contains(&"NUM_THREADS=256".to_string()));
+        assert!(args.contains(&"NUM_PARALLEL=4".to_string()));
+        assert!(args.contains(&"NO_AFFINITY=0".to_string()));
+
+        let args = Configure::default().make_args();
+        assert!(!args.iter().any(|arg| arg.starts_with("USE_THREAD=")));
+        let args = Configure::builder()
+            .use_thread(false)
+            .build()
+            .unwrap()
+            .make_args();
+        assert!(args.contains(&"USE_THREAD=0".to_string()));
+
+        // Single-threaded explicitly, since OpenBLAS may build the threaded library by default
+        let builder = Configure::builder().use_locking(true);
+        let locking = builder.clone().build().unwrap();
+        assert_eq!(locking.threaded(), Some(false));
+        let args = locking.make_args();
+        assert!(args.contains(&"USE_LOCKING=1".to_string()));
+        assert!(args.contains(&"USE_THREAD=0".to_string()));
+        assert!(!args.contains(&"USE_THREAD=1".to_string()));
+        assert_eq!(
+            builder.use_thread(true).build(),
+            Err(ConfigureError::LockingWithThread)
+        );
+        assert_eq!(
+            Configure::builder().num_parallel(2).build(),
+            Err(ConfigureError::RequiresThread {
+                option: "num_parallel"
+            })
+        );
+        assert_eq!(
+            Configure::builder().num_threads(0).build(),
+            Err(ConfigureError::ZeroThreads {
+                option: "num_threads"
+            })
+        );
+    }
+
+    #[test]
+    fn make_flags_debug() {
+        let mode = BuildMode {
+            debug: true,
+            sanitizers: vec![Sanitizer::Address],
+            ..Default::default()
+        };
+        let opt = Configure::builder().mode(mode).build().unwrap();
+        assert!(opt.make_args().contains(&"DEBUG=1".to_string()));
+        assert_eq!(
+            opt.make_env(),
+            vec![("COMMON_OPT", "-fsanitize=address".to_string())]
+        );
+    }
+
+    #[test]
+    fn make_jobs() {
+        let opt = Configure::default();
+        let jobserver = "-j --jobserver-fds=3,4 --jobserver-auth=3,4";
+        assert_eq!(
+            opt.make_jobs(Some(jobserver), Some("4")),
+            (
+                vec!["MAKE_NB_JOBS=0".to_string()],
+                vec![("MAKEFLAGS", jobserver.to_string())]
+            )
+        );
+        assert_eq!(
+            opt.make_jobs(None, Some("4")).0,
+            vec!["-j4", "MAKE_NB_JOBS=0"]
+        );
+        assert_eq!(opt.make_jobs(None, None), (Vec::new(), Vec::new()));
+
+        let opt = Configure::builder().jobs(2).build().unwrap();
+        assert_eq!(
+            opt.make_jobs(Some(jobserver), Some("4")),
+            (
+                vec!["-j2".to_string(), "MAKE_NB_JOBS=0".to_string()],
+                Vec::new()
+            )
+        );
+    }
+
+    #[test]
+    fn validate() {
+        assert!(Configure::default().validate().is_ok());
+
+        let opt = Configure {
+            no_static: true,
+            no_shared: true,
+            ..Default::default()
+        };
+        assert_eq!(opt.validate(), Err(ConfigureError::NoLibrary));
+
+        let mut opt = Configure {
+            no_lapack: true,
+            ..Default::default()
+        };
+        assert_eq!(opt.validate(), Err(ConfigureError::LapackeWithoutLapack));
+        opt.no_lapacke = true;
+        assert!(opt.validate().is_ok());
+
+        // LAPACK is not built without `netlib`
+        opt.goals = vec![MakeGoal::Libs, MakeGoal::Shared];
+        assert!(opt.validate().is_ok());
+        opt.no_lapack = false;
+        opt.no_lapacke = false;
+        assert_eq!(
+            opt.validate(),
+            Err(ConfigureError::MissingMakeGoal { goal: "netlib" })
+        );
+    }
+
+    #[test]
+    fn target_arch_mismatch() {
+        let mut opt = Configure {
+            dynamic_arch: true,
+            target: Some(Target::POWER9),
+            ..Default::default()
+        };
+        assert!(opt.validate_target_arch("powerpc64").is_ok());
+        assert!(matches!(
+            opt.validate_target_arch("x86_64"),
+            Err(Error::TargetArchMismatch { .. })
+        ));
+
+        opt.target = None;
+        opt.dynamic_list = vec![Target::HASWELL, Target::NEOVERSEN1];
+        assert!(opt.validate_target_arch("x86_64").is_err());
+    }
+
+    #[ignore]
+    #[test]
+    fn build_dynamic_arch() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let opt = Configure {
+            no_shared: true,
+            dynamic_arch: true,
+            dynamic_list: vec![Target::HASWELL, Target::ZEN, Target::SKYLAKEX],
+            ..Default::default()
+        };
+        let detail = opt
+            .build(
+                root.join("../openblas-src/source"),
+                root.join("test_build/build_dynamic_arch"),
+            )
+            .unwrap();
+        let static_lib = detail.static_lib.unwrap();
+        assert!(static_lib.has_dynamic_arch());
+        assert!(static_lib.has_kernels_for(&Target::ZEN));
+    }
 }
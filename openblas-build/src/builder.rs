@@ -0,0 +1,710 @@
+//! Constructors of [Configure]
+
+use crate::{build::*, error::*, mode::*, target::*, tuning::*};
+use std::{env, path::*};
+
+/// Builder of [Configure], validating the combination of options
+///
+/// Options not specified are same as [Configure::default].
+///
+/// ```
+/// use openblas_build::{error::*, *};
+/// let cfg = Configure::builder()
+///     .interface(Interface::ILP64)
+///     .target(Target::HASWELL)
+///     .use_thread(true)
+///     .build()
+///     .unwrap();
+/// assert_eq!(cfg.interface, Interface::ILP64);
+///
+/// // OpenMP is a threading model, and requires `use_thread`
+/// let err = Configure::builder().use_openmp(true).build().unwrap_err();
+/// assert_eq!(err, ConfigureError::OpenMPWithoutThread);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConfigureBuilder {
+    cfg: Configure,
+}
+
+impl ConfigureBuilder {
+    pub fn no_static(mut self, no_static: bool) -> Self {
+        self.cfg.no_static = no_static;
+        self
+    }
+
+    pub fn no_shared(mut self, no_shared: bool) -> Self {
+        self.cfg.no_shared = no_shared;
+        self
+    }
+
+    pub fn no_cblas(mut self, no_cblas: bool) -> Self {
+        self.cfg.no_cblas = no_cblas;
+        self
+    }
+
+    pub fn no_lapack(mut self, no_lapack: bool) -> Self {
+        self.cfg.no_lapack = no_lapack;
+        self
+    }
+
+    pub fn no_lapacke(mut self, no_lapacke: bool) -> Self {
+        self.cfg.no_lapacke = no_lapacke;
+        self
+    }
+
+    pub fn precisions(mut self, precisions: impl IntoIterator<Item = Precision>) -> Self {
+        self.cfg.precisions = precisions.into_iter().collect();
+        self
+    }
+
+    pub fn gc_sections(mut self, gc_sections: bool) -> Self {
+        self.cfg.gc_sections = gc_sections;
+        self
+    }
+
+    pub fn lapack_backend(mut self, lapack_backend: LapackBackend) -> Self {
+        self.cfg.lapack_backend = lapack_backend;
+        self
+    }
+
+    pub fn external_lapack(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cfg.external_lapack = Some(path.into());
+        self
+    }
+
+    pub fn use_thread(mut self, use_thread: bool) -> Self {
+        self.cfg.use_thread = Some(use_thread);
+        self
+    }
+
+    pub fn use_openmp(mut self, use_openmp: bool) -> Self {
+        self.cfg.use_openmp = use_openmp;
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.cfg.num_threads = Some(num_threads);
+        self
+    }
+
+    pub fn num_parallel(mut self, num_parallel: usize) -> Self {
+        self.cfg.num_parallel = Some(num_parallel);
+        self
+    }
+
+    pub fn use_locking(mut self, use_locking: bool) -> Self {
+        self.cfg.use_locking = use_locking;
+        self
+    }
+
+    pub fn no_affinity(mut self, no_affinity: bool) -> Self {
+        self.cfg.no_affinity = Some(no_affinity);
+        self
+    }
+
+    pub fn use_tls(mut self, use_tls: bool) -> Self {
+        self.cfg.use_tls = use_tls;
+        self
+    }
+
+    pub fn no_warmup(mut self, no_warmup: bool) -> Self {
+        self.cfg.no_warmup = no_warmup;
+        self
+    }
+
+    pub fn dynamic_arch(mut self, dynamic_arch: bool) -> Self {
+        self.cfg.dynamic_arch = dynamic_arch;
+        self
+    }
+
+    pub fn dynamic_list(mut self, targets: impl IntoIterator<Item = Target>) -> Self {
+        self.cfg.dynamic_list = targets.into_iter().collect();
+        self
+    }
+
+    pub fn dynamic_older(mut self, dynamic_older: bool) -> Self {
+        self.cfg.dynamic_older = dynamic_older;
+        self
+    }
+
+    pub fn interface(mut self, interface: Interface) -> Self {
+        self.cfg.interface = interface;
+        self
+    }
+
+    pub fn target(mut self, target: Target) -> Self {
+        self.cfg.target = Some(target);
+        self
+    }
+
+    pub fn cc(mut self, cc: impl Into<String>) -> Self {
+        self.cfg.cc = Some(cc.into());
+        self
+    }
+
+    pub fn fc(mut self, fc: impl Into<String>) -> Self {
+        self.cfg.fc = Some(fc.into());
+        self
+    }
+
+    pub fn c_compiler(mut self, c_compiler: CCompiler) -> Self {
+        self.cfg.c_compiler = Some(c_compiler);
+        self
+    }
+
+    pub fn f_compiler(mut self, f_compiler: FortranCompiler) -> Self {
+        self.cfg.f_compiler = Some(f_compiler);
+        self
+    }
+
+    pub fn ar(mut self, ar: impl Into<String>) -> Self {
+        self.cfg.ar = Some(ar.into());
+        self
+    }
+
+    pub fn cflags(mut self, flags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.cfg.cflags = flags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn common_opt(mut self, flags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.cfg.common_opt = flags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn fcommon_opt(mut self, flags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.cfg.fcommon_opt = flags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn ldflags(mut self, flags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.cfg.ldflags = flags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn hostcc(mut self, hostcc: impl Into<String>) -> Self {
+        self.cfg.hostcc = Some(hostcc.into());
+        self
+    }
+
+    pub fn binary(mut self, binary: Binary) -> Self {
+        self.cfg.binary = Some(binary);
+        self
+    }
+
+    pub fn cross_suffix(mut self, cross_suffix: impl Into<String>) -> Self {
+        self.cfg.cross_suffix = Some(cross_suffix.into());
+        self
+    }
+
+    pub fn sysroot(mut self, sysroot: impl Into<PathBuf>) -> Self {
+        self.cfg.sysroot = Some(sysroot.into());
+        self
+    }
+
+    pub fn tuning(mut self, tuning: Tuning) -> Self {
+        self.cfg.tuning = tuning;
+        self
+    }
+
+    pub fn mode(mut self, mode: BuildMode) -> Self {
+        self.cfg.mode = mode;
+        self
+    }
+
+    pub fn symbol_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.cfg.symbol_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn symbol_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.cfg.symbol_suffix = Some(suffix.into());
+        self
+    }
+
+    pub fn libname_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.cfg.libname_suffix = Some(suffix.into());
+        self
+    }
+
+    pub fn extra_args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.cfg.extra_args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn goals(mut self, goals: impl IntoIterator<Item = MakeGoal>) -> Self {
+        self.cfg.goals = goals.into_iter().collect();
+        self
+    }
+
+    pub fn install_prefix(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.cfg.install_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.cfg.jobs = Some(jobs);
+        self
+    }
+
+    /// Validate options, see [Configure::validate]
+    pub fn build(self) -> Result<Configure, ConfigureError> {
+        self.cfg.validate()?;
+        Ok(self.cfg)
+    }
+}
+
+impl Configure {
+    /// Environment variables read by [Configure::from_env],
+    /// e.g. to emit `cargo:rerun-if-env-changed` for them in build.rs
+    pub const ENV_VARS: &'static [&'static str] = &[
+        "OPENBLAS_NO_STATIC",
+        "OPENBLAS_NO_SHARED",
+        "OPENBLAS_NO_CBLAS",
+        "OPENBLAS_NO_LAPACK",
+        "OPENBLAS_NO_LAPACKE",
+        "OPENBLAS_BUILD_SINGLE",
+        "OPENBLAS_BUILD_DOUBLE",
+        "OPENBLAS_BUILD_COMPLEX",
+        "OPENBLAS_BUILD_COMPLEX16",
+        "OPENBLAS_GC_SECTIONS",
+        "OPENBLAS_LAPACK_BACKEND",
+        "OPENBLAS_EXTERNAL_LAPACK",
+        "OPENBLAS_USE_THREAD",
+        "OPENBLAS_USE_OPENMP",
+        "OPENBLAS_BUILD_NUM_THREADS",
+        "OPENBLAS_NUM_PARALLEL",
+        "OPENBLAS_USE_LOCKING",
+        "OPENBLAS_NO_AFFINITY",
+        "OPENBLAS_USE_TLS",
+        "OPENBLAS_NO_WARMUP",
+        "OPENBLAS_DYNAMIC_ARCH",
+        "OPENBLAS_DYNAMIC_OLDER",
+        "OPENBLAS_INTERFACE64",
+        "OPENBLAS_DYNAMIC_LIST",
+        "OPENBLAS_TARGET",
+        "OPENBLAS_CC",
+        "OPENBLAS_FC",
+        "OPENBLAS_C_COMPILER",
+        "OPENBLAS_F_COMPILER",
+        "OPENBLAS_AR",
+        "OPENBLAS_CFLAGS",
+        "OPENBLAS_COMMON_OPT",
+        "OPENBLAS_FCOMMON_OPT",
+        "OPENBLAS_LDFLAGS",
+        "OPENBLAS_HOSTCC",
+        "OPENBLAS_BINARY",
+        "OPENBLAS_CROSS_SUFFIX",
+        "OPENBLAS_SYSROOT",
+        "OPENBLAS_SMALL_MATRIX_OPT",
+        "OPENBLAS_GEMM_MULTITHREAD_THRESHOLD",
+        "OPENBLAS_BUFFERSIZE",
+        "OPENBLAS_HUGETLB_ALLOCATION",
+        "OPENBLAS_CONSISTENT_FPCSR",
+        "OPENBLAS_SANITIZERS",
+        "OPENBLAS_DEBUG",
+        "OPENBLAS_FRAME_POINTERS",
+        "OPENBLAS_SYMBOLPREFIX",
+        "OPENBLAS_SYMBOLSUFFIX",
+        "OPENBLAS_LIBNAMESUFFIX",
+        "OPENBLAS_ARGS",
+        "OPENBLAS_GOALS",
+        "OPENBLAS_INSTALL_PREFIX",
+        "OPENBLAS_JOBS",
+    ];
+
+    /// Read options from `OPENBLAS_*` environment variables
+    ///
+    /// Each option is read from the variable of the corresponding OpenBLAS `make` variable
+    /// with `OPENBLAS_` prefix, and options without variable are same as [Configure::default].
+    ///
+    /// | Variable                 | Option         | Value                                  |
+    /// |:-------------------------|:---------------|:---------------------------------------|
+    /// | `OPENBLAS_NO_STATIC`     | `no_static`    | `1` or `0` (also `true` or `false`)    |
+    /// | `OPENBLAS_NO_SHARED`     | `no_shared`    | same as above                          |
+    /// | `OPENBLAS_NO_CBLAS`      | `no_cblas`     | same as above                          |
+    /// | `OPENBLAS_NO_LAPACK`     | `no_lapack`    | same as above                          |
+    /// | `OPENBLAS_NO_LAPACKE`    | `no_lapacke`   | same as above                          |
+    /// | `OPENBLAS_BUILD_SINGLE`  | `precisions`   | `1` to add [Precision::Single]       |
+    /// | `OPENBLAS_BUILD_DOUBLE`  | `precisions`   | `1` to add [Precision::Double]       |
+    /// | `OPENBLAS_BUILD_COMPLEX` | `precisions`   | `1` to add [Precision::Complex]      |
+    /// | `OPENBLAS_BUILD_COMPLEX16` | `precisions` | `1` to add [Precision::Complex16]    |
+    /// | `OPENBLAS_GC_SECTIONS`   | `gc_sections`  | `1` or `0`                             |
+    /// | `OPENBLAS_LAPACK_BACKEND`| `lapack_backend` | `fortran`, `c` (`C_LAPACK=1`) or `relapack` (`BUILD_RELAPACK=1`) |
+    /// | `OPENBLAS_EXTERNAL_LAPACK` | `external_lapack` | path of LAPACK library, e.g. `/path/to/liblapack.a`. Implies `no_lapack` and `no_lapacke` |
+    /// | `OPENBLAS_USE_THREAD`    | `use_thread`   | `1` or `0`, decided by OpenBLAS if unset |
+    /// | `OPENBLAS_USE_OPENMP`    | `use_openmp`   | `1` or `0`                             |
+    /// | `OPENBLAS_BUILD_NUM_THREADS` | `num_threads` | positive integer                   |
+    /// | `OPENBLAS_NUM_PARALLEL`  | `num_parallel` | positive integer                       |
+    /// | `OPENBLAS_USE_LOCKING`   | `use_locking`  | `1` or `0`                             |
+    /// | `OPENBLAS_NO_AFFINITY`   | `no_affinity`  | `1` or `0`                             |
+    /// | `OPENBLAS_USE_TLS`       | `use_tls`      | `1` or `0`                             |
+    /// | `OPENBLAS_NO_WARMUP`     | `no_warmup`    | `1` or `0`                             |
+    /// | `OPENBLAS_DYNAMIC_ARCH`  | `dynamic_arch` | same as above                          |
+    /// | `OPENBLAS_DYNAMIC_LIST`  | `dynamic_list` | targets separated by space, e.g. `HASWELL SKYLAKEX` |
+    /// | `OPENBLAS_DYNAMIC_OLDER` | `dynamic_older`| `1` or `0`                             |
+    /// | `OPENBLAS_INTERFACE64`   | `interface`    | `1` for ILP64, `0` for LP64            |
+    /// | `OPENBLAS_TARGET`        | `target`       | target name, e.g. `SKYLAKEX`           |
+    /// | `OPENBLAS_CC`            | `cc`           | command                                |
+    /// | `OPENBLAS_FC`            | `fc`           | command                                |
+    /// | `OPENBLAS_C_COMPILER`    | `c_compiler`   | `gcc` or `clang`                       |
+    /// | `OPENBLAS_F_COMPILER`    | `f_compiler`   | `gfortran` or `flang`                  |
+    /// | `OPENBLAS_AR`            | `ar`           | command                                |
+    /// | `OPENBLAS_CFLAGS`        | `cflags`       | flags separated by space               |
+    /// | `OPENBLAS_COMMON_OPT`    | `common_opt`   | flags separated by space               |
+    /// | `OPENBLAS_FCOMMON_OPT`   | `fcommon_opt`  | flags separated by space               |
+    /// | `OPENBLAS_LDFLAGS`       | `ldflags`      | flags separated by space               |
+    /// | `OPENBLAS_HOSTCC`        | `hostcc`       | command                                |
+    /// | `OPENBLAS_BINARY`        | `binary`       | `32` or `64`                           |
+    /// | `OPENBLAS_CROSS_SUFFIX`  | `cross_suffix` | prefix of binutils, e.g. `aarch64-linux-gnu-` |
+    /// | `OPENBLAS_SYSROOT`       | `sysroot`      | path                                   |
+    /// | `OPENBLAS_SMALL_MATRIX_OPT` | `tuning.small_matrix_opt` | `1` or `0`                 |
+    /// | `OPENBLAS_GEMM_MULTITHREAD_THRESHOLD` | `tuning.gemm_multithread_threshold` | positive integer |
+    /// | `OPENBLAS_BUFFERSIZE`    | `tuning.buffersize` | integer from 20 to 30             |
+    /// | `OPENBLAS_HUGETLB_ALLOCATION` | `tuning.hugetlb_allocation` | `1` or `0`             |
+    /// | `OPENBLAS_CONSISTENT_FPCSR` | `tuning.consistent_fpcsr` | `1` or `0`                 |
+    /// | `OPENBLAS_DEBUG`         | `mode.debug`   | `1` or `0`                             |
+    /// | `OPENBLAS_SANITIZERS`    | `mode.sanitizers` | `address`, `thread` or `undefined` separated by comma |
+    /// | `OPENBLAS_FRAME_POINTERS`| `mode.frame_pointers` | `1` or `0`                     |
+    /// | `OPENBLAS_SYMBOLPREFIX`  | `symbol_prefix`| prefix of symbols, e.g. `oblas_`       |
+    /// | `OPENBLAS_SYMBOLSUFFIX`  | `symbol_suffix`| suffix of symbols                      |
+    /// | `OPENBLAS_LIBNAMESUFFIX` | `libname_suffix`| suffix of library name, e.g. `64` for `libopenblas_64` |
+    /// | `OPENBLAS_ARGS`          | `extra_args`   | arguments separated by space           |
+    /// | `OPENBLAS_GOALS`         | `goals`        | goals separated by space, e.g. `libs netlib shared tests` |
+    /// | `OPENBLAS_INSTALL_PREFIX`| `install_prefix` | path                                 |
+    /// | `OPENBLAS_JOBS`          | `jobs`         | positive integer                       |
+    ///
+    /// `OPENBLAS_NUM_THREADS` is not used for `num_threads`, since it is the runtime option of OpenBLAS.
+    /// All variables are listed in [Configure::ENV_VARS].
+    ///
+    /// Target names unknown to this crate are read as [Target::Custom],
+    /// and checked in [Configure::build] using `TargetList.txt` of the OpenBLAS source.
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// [Configure::from_env] with the variables looked up by `var`
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Error> {
+        let env = EnvVars(var);
+        let mut builder = Configure::builder();
+        if let Some(no_static) = env.bool("OPENBLAS_NO_STATIC")? {
+            builder = builder.no_static(no_static);
+        }
+        if let Some(no_shared) = env.bool("OPENBLAS_NO_SHARED")? {
+            builder = builder.no_shared(no_shared);
+        }
+        if let Some(no_cblas) = env.bool("OPENBLAS_NO_CBLAS")? {
+            builder = builder.no_cblas(no_cblas);
+        }
+        if let Some(no_lapack) = env.bool("OPENBLAS_NO_LAPACK")? {
+            builder = builder.no_lapack(no_lapack);
+        }
+        if let Some(no_lapacke) = env.bool("OPENBLAS_NO_LAPACKE")? {
+            builder = builder.no_lapacke(no_lapacke);
+        }
+        let mut precisions = Vec::new();
+        for precision in &Precision::ALL {
+            let name = format!("OPENBLAS_{}", precision.make_var());
+            if env.bool(&name)? == Some(true) {
+                precisions.push(*precision);
+            }
+        }
+        builder = builder.precisions(precisions);
+        if let Some(gc_sections) = env.bool("OPENBLAS_GC_SECTIONS")? {
+            builder = builder.gc_sections(gc_sections);
+        }
+        if let Some(name) = env.var("OPENBLAS_LAPACK_BACKEND") {
+            let lapack_backend =
+                LapackBackend::from_name(name.trim()).ok_or_else(|| Error::InvalidEnvVar {
+                    name: "OPENBLAS_LAPACK_BACKEND".into(),
+                    value: name.clone(),
+                })?;
+            builder = builder.lapack_backend(lapack_backend);
+        }
+        if let Some(path) = env.var("OPENBLAS_EXTERNAL_LAPACK") {
+            // LAPACK and LAPACKE are not built, since the external library provides LAPACK
+            builder = builder
+                .external_lapack(path.trim())
+                .no_lapack(true)
+                .no_lapacke(true);
+        }
+        if let Some(use_thread) = env.bool("OPENBLAS_USE_THREAD")? {
+            builder = builder.use_thread(use_thread);
+        }
+        if let Some(use_openmp) = env.bool("OPENBLAS_USE_OPENMP")? {
+            builder = builder.use_openmp(use_openmp);
+        }
+        if let Some(num_threads) = env.usize("OPENBLAS_BUILD_NUM_THREADS")? {
+            builder = builder.num_threads(num_threads);
+        }
+        if let Some(num_parallel) = env.usize("OPENBLAS_NUM_PARALLEL")? {
+            builder = builder.num_parallel(num_parallel);
+        }
+        if let Some(use_locking) = env.bool("OPENBLAS_USE_LOCKING")? {
+            builder = builder.use_locking(use_locking);
+        }
+        if let Some(no_affinity) = env.bool("OPENBLAS_NO_AFFINITY")? {
+            builder = builder.no_affinity(no_affinity);
+        }
+        if let Some(use_tls) = env.bool("OPENBLAS_USE_TLS")? {
+            builder = builder.use_tls(use_tls);
+        }
+        if let Some(no_warmup) = env.bool("OPENBLAS_NO_WARMUP")? {
+            builder = builder.no_warmup(no_warmup);
+        }
+        if let Some(dynamic_arch) = env.bool("OPENBLAS_DYNAMIC_ARCH")? {
+            builder = builder.dynamic_arch(dynamic_arch);
+        }
+        if let Some(dynamic_older) = env.bool("OPENBLAS_DYNAMIC_OLDER")? {
+            builder = builder.dynamic_older(dynamic_older);
+        }
+        if let Some(ilp64) = env.bool("OPENBLAS_INTERFACE64")? {
+            builder = builder.interface(if ilp64 {
+                Interface::ILP64
+            } else {
+                Interface::LP64
+            });
+        }
+        if let Some(list) = env.var("OPENBLAS_DYNAMIC_LIST") {
+            builder = builder.dynamic_list(list.split_whitespace().map(target_from_env));
+        }
+        if let Some(target) = env.var("OPENBLAS_TARGET") {
+            builder = builder.target(target_from_env(&target));
+        }
+        if let Some(cc) = env.var("OPENBLAS_CC") {
+            builder = builder.cc(cc);
+        }
+        if let Some(fc) = env.var("OPENBLAS_FC") {
+            builder = builder.fc(fc);
+        }
+        if let Some(name) = env.var("OPENBLAS_C_COMPILER") {
+            let c_compiler =
+                CCompiler::from_name(name.trim()).ok_or_else(|| Error::InvalidEnvVar {
+                    name: "OPENBLAS_C_COMPILER".into(),
+                    value: name.clone(),
+                })?;
+            builder = builder.c_compiler(c_compiler);
+        }
+        if let Some(name) = env.var("OPENBLAS_F_COMPILER") {
+            let f_compiler =
+                FortranCompiler::from_name(name.trim()).ok_or_else(|| Error::InvalidEnvVar {
+                    name: "OPENBLAS_F_COMPILER".into(),
+                    value: name.clone(),
+                })?;
+            builder = builder.f_compiler(f_compiler);
+        }
+        if let Some(ar) = env.var("OPENBLAS_AR") {
+            builder = builder.ar(ar);
+        }
+        if let Some(cflags) = env.var("OPENBLAS_CFLAGS") {
+            builder = builder.cflags(cflags.split_whitespace());
+        }
+        if let Some(flags) = env.var("OPENBLAS_COMMON_OPT") {
+            builder = builder.common_opt(flags.split_whitespace());
+        }
+        if let Some(flags) = env.var("OPENBLAS_FCOMMON_OPT") {
+            builder = builder.fcommon_opt(flags.split_whitespace());
+        }
+        if let Some(flags) = env.var("OPENBLAS_LDFLAGS") {
+            builder = builder.ldflags(flags.split_whitespace());
+        }
+        if let Some(hostcc) = env.var("OPENBLAS_HOSTCC") {
+            builder = builder.hostcc(hostcc);
+        }
+        if let Some(binary) = env.var("OPENBLAS_BINARY") {
+            builder = builder.binary(match binary.trim() {
+                "32" => Binary::B32,
+                "64" => Binary::B64,
+                _ => {
+                    return Err(Error::InvalidEnvVar {
+                        name: "OPENBLAS_BINARY".into(),
+                        value: binary,
+                    })
+                }
+            });
+        }
+        if let Some(cross_suffix) = env.var("OPENBLAS_CROSS_SUFFIX") {
+            builder = builder.cross_suffix(cross_suffix.trim());
+        }
+        if let Some(sysroot) = env.var("OPENBLAS_SYSROOT") {
+            builder = builder.sysroot(sysroot.trim());
+        }
+        let tuning = Tuning {
+            small_matrix_opt: env.bool("OPENBLAS_SMALL_MATRIX_OPT")?,
+            gemm_multithread_threshold: env.usize("OPENBLAS_GEMM_MULTITHREAD_THRESHOLD")?,
+            buffersize: env.usize("OPENBLAS_BUFFERSIZE")?,
+            hugetlb_allocation: env.bool("OPENBLAS_HUGETLB_ALLOCATION")?.unwrap_or(false),
+            consistent_fpcsr: env.bool("OPENBLAS_CONSISTENT_FPCSR")?.unwrap_or(false),
+        };
+        builder = builder.tuning(tuning);
+        let mut sanitizers = Vec::new();
+        if let Some(names) = env.var("OPENBLAS_SANITIZERS") {
+            for name in names.split(',') {
+                sanitizers.push(Sanitizer::from_name(name.trim()).ok_or_else(|| {
+                    Error::InvalidEnvVar {
+                        name: "OPENBLAS_SANITIZERS".into(),
+                        value: names.clone(),
+                    }
+                })?);
+            }
+        }
+        builder = builder.mode(BuildMode {
+            debug: env.bool("OPENBLAS_DEBUG")?.unwrap_or(false),
+            sanitizers,
+            frame_pointers: env.bool("OPENBLAS_FRAME_POINTERS")?.unwrap_or(false),
+        });
+        if let Some(prefix) = env.var("OPENBLAS_SYMBOLPREFIX") {
+            builder = builder.symbol_prefix(prefix.trim());
+        }
+        if let Some(suffix) = env.var("OPENBLAS_SYMBOLSUFFIX") {
+            builder = builder.symbol_suffix(suffix.trim());
+        }
+        if let Some(suffix) = env.var("OPENBLAS_LIBNAMESUFFIX") {
+            builder = builder.libname_suffix(suffix.trim());
+        }
+        if let Some(args) = env.var("OPENBLAS_ARGS") {
+            builder = builder.extra_args(args.split_whitespace());
+        }
+        if let Some(names) = env.var("OPENBLAS_GOALS") {
+            let goals = names
+                .split_whitespace()
+                .map(|name| {
+                    MakeGoal::from_name(name).ok_or_else(|| Error::InvalidEnvVar {
+                        name: "OPENBLAS_GOALS".into(),
+                        value: names.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            builder = builder.goals(goals);
+        }
+        if let Some(prefix) = env.var("OPENBLAS_INSTALL_PREFIX") {
+            builder = builder.install_prefix(prefix.trim());
+        }
+        if let Some(jobs) = env.usize("OPENBLAS_JOBS")? {
+            builder = builder.jobs(jobs);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// Lookup of environment variables, which is replaced in tests
+struct EnvVars<F>(F);
+
+impl<F: Fn(&str) -> Option<String>> EnvVars<F> {
+    /// Non-empty value of environment variable
+    fn var(&self, name: &str) -> Option<String> {
+        (self.0)(name).filter(|value| !value.trim().is_empty())
+    }
+
+    fn bool(&self, name: &str) -> Result<Option<bool>, Error> {
+        let value = match self.var(name) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match value.trim().to_lowercase().as_str() {
+            "1" | "true" => Ok(Some(true)),
+            "0" | "false" => Ok(Some(false)),
+            _ => Err(Error::InvalidEnvVar {
+                name: name.into(),
+                value,
+            }),
+        }
+    }
+
+    fn usize(&self, name: &str) -> Result<Option<usize>, Error> {
+        match self.var(name) {
+            Some(value) => value
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|_| Error::InvalidEnvVar {
+                    name: name.into(),
+                    value,
+                }),
+            None => Ok(None),
+        }
+    }
+}
+
+fn target_from_env(name: &str) -> Target {
+    name.parse().unwrap_or_else(|_| Target::custom(name.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_default() {
+        assert_eq!(Configure::builder().build().unwrap(), Configure::default());
+    }
+
+    #[test]
+    fn reject_dynamic_list_without_dynamic_arch() {
+        let builder = Configure::builder().dynamic_list(vec![Target::HASWELL, Target::ZEN]);
+        assert_eq!(
+            builder.clone().build(),
+            Err(ConfigureError::RequiresDynamicArch {
+                option: "dynamic_list"
+            })
+        );
+        assert!(builder.dynamic_arch(true).build().is_ok());
+    }
+
+    #[test]
+    fn from_env() {
+        let mut vars = vec![
+            ("OPENBLAS_USE_THREAD", "1"),
+            ("OPENBLAS_INTERFACE64", "true"),
+            ("OPENBLAS_TARGET", "skylakex"),
+            ("OPENBLAS_CC", "clang"),
+            ("OPENBLAS_ARGS", "NUM_THREADS=8  NO_AFFINITY=1"),
+        ];
+        let lookup = |vars: &[(&str, &str)], name: &str| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        };
+        let cfg = Configure::from_vars(|name| lookup(&vars, name)).unwrap();
+        assert_eq!(cfg.use_thread, Some(true));
+        assert_eq!(cfg.interface, Interface::ILP64);
+        assert_eq!(cfg.target, Some(Target::SKYLAKEX));
+        assert_eq!(cfg.cc.as_deref(), Some("clang"));
+        assert_eq!(cfg.extra_args, vec!["NUM_THREADS=8", "NO_AFFINITY=1"]);
+
+        vars[0] = ("OPENBLAS_USE_THREAD", "yes");
+        assert!(matches!(
+            Configure::from_vars(|name| lookup(&vars, name)),
+            Err(Error::InvalidEnvVar { .. })
+        ));
+    }
+
+    #[test]
+    fn external_lapack_from_env() {
+        let cfg = Configure::from_vars(|name| {
+            if name == "OPENBLAS_EXTERNAL_LAPACK" {
+                Some("/path/to/liblapack.a".into())
+            } else {
+                None
+            }
+        })
+        .unwrap();
+        assert_eq!(
+            cfg.external_lapack,
+            Some(PathBuf::from("/path/to/liblapack.a"))
+        );
+        assert!(cfg.no_lapack);
+        assert!(cfg.no_lapacke);
+    }
+
+    #[test]
+    fn env_vars() {
+        let read = std::cell::RefCell::new(Vec::new());
+        Configure::from_vars(|name| {
+            read.borrow_mut().push(name.to_string());
+            None
+        })
+        .unwrap();
+        assert_eq!(read.into_inner(), Configure::ENV_VARS);
+    }
+}
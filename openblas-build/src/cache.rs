@@ -0,0 +1,116 @@
+//! Stable key for caching builds
+
+use crate::{build::*, error::*};
+use std::{env, path::*};
+
+/// Bump this when the layout of cached build changes
+const CACHE_VERSION: &str = "1";
+
+/// 64-bit [FNV-1a](http://www.isthe.com/chongo/tech/comp/fnv/) hash
+///
+/// Unlike `std::collections::hash_map::DefaultHasher`,
+/// this is stable across Rust releases and platforms.
+fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+impl Configure {
+    /// Inputs of [Configure::cache_key] as `key = value` lines
+    fn cache_inputs(&self, openblas_root: &Path) -> Result<String, Error> {
+        let cc = self.cc_command().unwrap_or_else(|| "cc".into());
+        // OpenBLAS searches gfortran first if FC is not specified
+        let fc = self.fc_command().unwrap_or_else(|| "gfortran".into());
+        let mut inputs = String::new();
+        let mut push = |key: &str, value: &str| {
+            inputs.push_str(&format!("{} = {}\n", key, value));
+        };
+        push("cache_version", CACHE_VERSION);
+        push("rust_target", &env::var("TARGET").unwrap_or_default());
+        push("cc", &cc);
+        push("cc_version", &compiler_version(&cc).unwrap_or_default());
+        push("fc", &fc);
+        push("fc_version", &compiler_version(&fc).unwrap_or_default());
+        push(
+            "openblas_version",
+            &openblas_version(openblas_root).unwrap_or_default(),
+        );
+        inputs.push_str(&toml::to_string(&self.to_build_table()?)?);
+        Ok(inputs)
+    }
+
+    /// Stable key identifying the build, e.g. to name a shared cache directory
+    ///
+    /// This is a 64-bit FNV-1a hash in hexadecimal over
+    ///
+    /// - all options of this configuration (see [Configure::to_toml]) except `jobs`,
+    /// - the Rust target triple in `TARGET` environment variable set by cargo,
+    /// - the C and Fortran compiler commands and the first lines of their `--version`, and
+    /// - the OpenBLAS version in `Makefile.rule` of `openblas_root`.
+    ///
+    /// The hash does not depend on the Rust version, and is same across builds
+    /// as long as these inputs are same.
+    ///
+    /// Fails only if the options cannot be serialized into TOML, e.g. a path is not UTF-8.
+    pub fn cache_key(&self, openblas_root: impl AsRef<Path>) -> Result<String, Error> {
+        Ok(format!(
+            "{:016x}",
+            fnv1a64(self.cache_inputs(openblas_root.as_ref())?.as_bytes())
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::*;
+
+    #[test]
+    fn fnv1a64_test_vectors() {
+        assert_eq!(fnv1a64(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a64(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a64(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn cache_key() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../openblas-src/source");
+        let cfg = Configure::default();
+        assert_eq!(cfg.cache_key(&root).unwrap(), cfg.cache_key(&root).unwrap());
+
+        let ilp64 = Configure::builder()
+            .interface(Interface::ILP64)
+            .build()
+            .unwrap();
+        assert_ne!(
+            cfg.cache_key(&root).unwrap(),
+            ilp64.cache_key(&root).unwrap()
+        );
+
+        let clang = Configure::builder().cc("clang").build().unwrap();
+        assert_ne!(
+            cfg.cache_key(&root).unwrap(),
+            clang.cache_key(&root).unwrap()
+        );
+
+        let debug = Configure::builder()
+            .mode(BuildMode::from_cargo("debug", "true", ""))
+            .build()
+            .unwrap();
+        assert_ne!(
+            cfg.cache_key(&root).unwrap(),
+            debug.cache_key(&root).unwrap()
+        );
+
+        // Parallelism does not change the deliverables
+        let jobs = Configure::builder().jobs(4).build().unwrap();
+        assert_eq!(
+            cfg.cache_key(&root).unwrap(),
+            jobs.cache_key(&root).unwrap()
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Compiler environment variables in the same manner as [cc-rs](https://github.com/rust-lang/cc-rs)
+
+use crate::build::*;
+use std::env;
+
+/// Variables read by [Configure::fill_cc_env]
+const CC_ENV_NAMES: &[&str] = &["CC", "AR", "CFLAGS"];
+
+/// Variables for `name` in the order of cc-rs
+///
+/// For `name = "CC"`, `target = "aarch64-unknown-linux-gnu"`:
+///
+/// 1. `CC_aarch64-unknown-linux-gnu`
+/// 2. `CC_aarch64_unknown_linux_gnu`
+/// 3. `TARGET_CC`, or `HOST_CC` if `target == host`
+/// 4. `CC` only if `target == host`, since it is usually for the host in cross builds
+fn vars_for_target(name: &str, target: &str, host: &str) -> Vec<String> {
+    let mut vars = vec![
+        format!("{}_{}", name, target),
+        format!("{}_{}", name, target.replace('-', "_")),
+    ];
+    if target == host {
+        vars.push(format!("HOST_{}", name));
+        vars.push(name.to_string());
+    } else {
+        vars.push(format!("TARGET_{}", name));
+    }
+    vars
+}
+
+impl Configure {
+    /// Environment variables read by [Configure::fill_cc_env],
+    /// e.g. to emit `cargo:rerun-if-env-changed` for them in build.rs
+    pub fn cc_env_vars(target_triple: &str, host_triple: &str) -> Vec<String> {
+        CC_ENV_NAMES
+            .iter()
+            .flat_map(|name| vars_for_target(name, target_triple, host_triple))
+            .collect()
+    }
+
+    /// Fill `cc`, `ar` and `cflags` not specified yet from environment variables
+    /// `CC`, `AR` and `CFLAGS` resolved in the same manner as cc-rs,
+    /// i.e. `CC_<target>`, `CC_<target with underscores>`, `TARGET_CC` (`HOST_CC` for native build),
+    /// and `CC` only for native build, so that `CC` for the host is not used to cross-compile.
+    ///
+    /// `cc` is not filled if `c_compiler` is specified, which has priority over the environment.
+    /// The Fortran compiler is not read from the environment, and is `fc` or `f_compiler` only.
+    /// `CFLAGS` is split by whitespace as cc-rs does, i.e. a flag cannot contain spaces even if quoted.
+    ///
+    /// [Configure::build] does not pass `CC`, `AR` and `CFLAGS` in its environment to `make`,
+    /// and use only those in `self`.
+    pub fn fill_cc_env(&mut self, target_triple: &str, host_triple: &str) {
+        self.fill_cc_vars(target_triple, host_triple, |name| env::var(name).ok())
+    }
+
+    /// [Configure::fill_cc_env] with the variables looked up by `var`
+    fn fill_cc_vars(&mut self, target: &str, host: &str, var: impl Fn(&str) -> Option<String>) {
+        // Non-empty value of the first variable found
+        let find = |name: &str| {
+            vars_for_target(name, target, host)
+                .iter()
+                .find_map(|name| var(name).filter(|value| !value.trim().is_empty()))
+        };
+        if self.cc.is_none() && self.c_compiler.is_none() {
+            self.cc = find("CC");
+        }
+        if self.ar.is_none() {
+            self.ar = find("AR");
+        }
+        if self.cflags.is_empty() {
+            if let Some(cflags) = find("CFLAGS") {
+                self.cflags = cflags.split_whitespace().map(Into::into).collect();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "aarch64-unknown-linux-gnu";
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    fn lookup<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn fill_cc_env() {
+        let vars = [
+            ("CC_aarch64-unknown-linux-gnu", "clang"),
+            ("CC_aarch64_unknown_linux_gnu", "gcc"),
+            ("AR_aarch64_unknown_linux_gnu", "llvm-ar"),
+            ("CFLAGS_aarch64_unknown_linux_gnu", "-O2  -g"),
+        ];
+        let mut cfg = Configure::default();
+        cfg.fill_cc_vars(TARGET, HOST, lookup(&vars));
+        assert_eq!(cfg.cc.as_deref(), Some("clang"));
+        assert_eq!(cfg.ar.as_deref(), Some("llvm-ar"));
+        assert_eq!(cfg.cflags, vec!["-O2", "-g"]);
+
+        // Specified options are kept
+        let mut cfg = Configure::builder().cc("cc").build().unwrap();
+        cfg.fill_cc_vars(TARGET, HOST, lookup(&vars));
+        assert_eq!(cfg.cc.as_deref(), Some("cc"));
+
+        // `c_compiler` has priority over `CC` in the environment
+        let mut cfg = Configure::builder()
+            .c_compiler(CCompiler::Clang)
+            .build()
+            .unwrap();
+        cfg.fill_cc_vars(TARGET, HOST, lookup(&vars));
+        assert_eq!(cfg.cc, None);
+        assert_eq!(cfg.cc_command().as_deref(), Some("clang"));
+        assert_eq!(cfg.ar.as_deref(), Some("llvm-ar"));
+        cfg.fill_cross_defaults(TARGET, HOST);
+        assert_eq!(cfg.cc.as_deref(), Some("clang --target=aarch64-linux-gnu"));
+    }
+
+    #[test]
+    fn host_cc_in_cross_build() {
+        let vars = [("CC", "gcc"), ("HOST_CC", "gcc"), ("AR", "ar")];
+        // `CC` for the host is not used to cross-compile, and the cross defaults are kept
+        let mut cfg = Configure::default();
+        cfg.fill_cc_vars(TARGET, HOST, lookup(&vars));
+        assert_eq!(cfg.cc, None);
+        assert_eq!(cfg.ar, None);
+        cfg.fill_cross_defaults(TARGET, HOST);
+        assert_eq!(cfg.cc.as_deref(), Some("aarch64-linux-gnu-gcc"));
+
+        let vars = [
+            ("CC", "gcc"),
+            ("TARGET_CC", "clang --target=aarch64-linux-gnu"),
+        ];
+        let mut cfg = Configure::default();
+        cfg.fill_cc_vars(TARGET, HOST, lookup(&vars));
+        assert_eq!(cfg.cc.as_deref(), Some("clang --target=aarch64-linux-gnu"));
+
+        // but used for native build
+        let mut cfg = Configure::default();
+        cfg.fill_cc_vars(HOST, HOST, lookup(&vars));
+        assert_eq!(cfg.cc.as_deref(), Some("gcc"));
+    }
+
+    #[test]
+    fn cc_env_vars() {
+        let vars = Configure::cc_env_vars(TARGET, HOST);
+        assert!(vars.contains(&"CFLAGS_aarch64_unknown_linux_gnu".to_string()));
+        assert!(vars.contains(&"TARGET_AR".to_string()));
+        assert!(!vars.contains(&"CC".to_string()));
+        assert!(Configure::cc_env_vars(HOST, HOST).contains(&"CC".to_string()));
+    }
+}
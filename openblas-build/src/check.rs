@@ -1,6 +1,7 @@
 //! Check make results
 
-use crate::error::*;
+use crate::{error::*, target::Target};
+use serde::Serialize;
 use std::{
     collections::HashSet,
     fs,
@@ -20,7 +21,7 @@ use std::{
 /// let info = LinkFlags::parse("-L/usr/lib/gcc/x86_64-pc-linux-gnu/10.2.0 -L/usr/lib/gcc/x86_64-pc-linux-gnu/10.2.0/../../../../lib -L/lib/../lib -L/usr/lib/../lib -L/usr/lib/gcc/x86_64-pc-linux-gnu/10.2.0/../../..  -lc").unwrap();
 /// assert_eq!(info.libs, vec!["c"]);
 /// ```
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct LinkFlags {
     /// Existing paths specified by `-L`
     pub search_paths: Vec<PathBuf>,
@@ -61,9 +62,19 @@ impl LinkFlags {
 }
 
 /// Parse Makefile.conf which generated by OpenBLAS make system
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct MakeConf {
     pub os_name: String,
+    /// Architecture, e.g. `x86_64`
+    pub arch: String,
+    /// Target CPU which OpenBLAS is built for, e.g. `HASWELL`
+    pub core: String,
+    /// C compiler family detected by OpenBLAS, e.g. `GCC` or `CLANG`
+    pub c_compiler: String,
+    /// Fortran compiler family detected by OpenBLAS, e.g. `GFORTRAN` or `FLANG`
+    pub f_compiler: String,
+    /// Fortran compiler, empty if `no_fortran`
+    pub fc: String,
     pub no_fortran: bool,
     pub c_extra_libs: LinkFlags,
     pub f_extra_libs: LinkFlags,
@@ -79,7 +90,7 @@ impl MakeConf {
         let buf = io::BufReader::new(f);
         for line in buf.lines() {
             let line = line.expect("Makefile.conf should not include non-UTF8 string");
-            if line.len() == 0 {
+            if line.is_empty() {
                 continue;
             }
             let entry: Vec<_> = line.split("=").collect();
@@ -88,6 +99,11 @@ impl MakeConf {
             }
             match entry[0] {
                 "OSNAME" => detail.os_name = entry[1].into(),
+                "ARCH" => detail.arch = entry[1].into(),
+                "CORE" => detail.core = entry[1].into(),
+                "C_COMPILER" => detail.c_compiler = entry[1].into(),
+                "F_COMPILER" => detail.f_compiler = entry[1].into(),
+                "FC" => detail.fc = entry[1].into(),
                 "NOFORTRAN" => detail.no_fortran = true,
                 "CEXTRALIB" => detail.c_extra_libs = LinkFlags::parse(entry[1])?,
                 "FEXTRALIB" => detail.f_extra_libs = LinkFlags::parse(entry[1])?,
@@ -107,6 +123,18 @@ pub struct LibInspect {
     path: PathBuf,
     pub libs: Vec<String>,
     pub symbols: Vec<String>,
+    symbol_prefix: String,
+    symbol_suffix: String,
+}
+
+/// `[prefix][name]` if it can be executed, otherwise `name`
+fn binutil(prefix: &str, name: &str) -> String {
+    let prefixed = format!("{}{}", prefix, name);
+    if !prefix.is_empty() && Command::new(&prefixed).arg("--version").output().is_ok() {
+        prefixed
+    } else {
+        name.into()
+    }
 }
 
 impl LibInspect {
@@ -114,6 +142,13 @@ impl LibInspect {
     ///
     /// Be sure that `nm -g` and `objdump -p` are executed in this function
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::with_binutils(path, "")
+    }
+
+    /// Inspect library file using binutils with `prefix`, e.g. `aarch64-linux-gnu-nm`
+    ///
+    /// Falls back to `nm` and `objdump` of the host if those with `prefix` are not found.
+    pub fn with_binutils<P: AsRef<Path>>(path: P, prefix: &str) -> Result<Self, Error> {
         let path = path.as_ref();
         if !path.exists() {
             return Err(Error::LibraryNotExist {
@@ -121,7 +156,10 @@ impl LibInspect {
             });
         }
 
-        let nm_out = Command::new("nm").arg("-g").arg(path).output()?;
+        let nm_out = Command::new(binutil(prefix, "nm"))
+            .arg("-g")
+            .arg(path)
+            .output()?;
 
         // assumes `nm` output like following:
         //
@@ -143,7 +181,7 @@ impl LibInspect {
             .collect();
         symbols.sort(); // sort alphabetically
 
-        let mut libs: Vec<_> = Command::new("objdump")
+        let mut libs: Vec<_> = Command::new(binutil(prefix, "objdump"))
             .arg("-p")
             .arg(path)
             .output()?
@@ -164,34 +202,89 @@ impl LibInspect {
             path: path.into(),
             libs,
             symbols,
+            symbol_prefix: String::new(),
+            symbol_suffix: String::new(),
         })
     }
 
+    /// Check symbols renamed by `SYMBOLPREFIX` and `SYMBOLSUFFIX` in `has_*` methods,
+    /// e.g. `oblas_dsyev_` for `prefix = "oblas_"`
+    pub fn with_symbol_affix(mut self, prefix: &str, suffix: &str) -> Self {
+        self.symbol_prefix = prefix.into();
+        self.symbol_suffix = suffix.into();
+        self
+    }
+
+    /// Original name of the symbol, None if it is not renamed by the prefix and suffix
+    fn strip_affix<'a>(&self, sym: &'a str) -> Option<&'a str> {
+        sym.strip_prefix(self.symbol_prefix.as_str())?
+            .strip_suffix(self.symbol_suffix.as_str())
+    }
+
+    /// Path of the inspected library
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check the library exports the function, e.g. `dgemm_`, taking the symbol prefix and suffix into account
+    pub fn has_function(&self, name: &str) -> bool {
+        self.symbols
+            .iter()
+            .any(|sym| self.strip_affix(sym) == Some(name))
+    }
+
     pub fn has_cblas(&self) -> bool {
         for sym in &self.symbols {
-            if sym.starts_with("cblas_") {
+            if let Some(true) = self.strip_affix(sym).map(|sym| sym.starts_with("cblas_")) {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     pub fn has_lapack(&self) -> bool {
         for sym in &self.symbols {
-            if sym == "dsyev_" {
+            if self.strip_affix(sym) == Some("dsyev_") {
                 return true;
             }
         }
-        return false;
+        false
+    }
+
+    /// Check the library contains ReLAPACK of `BUILD_RELAPACK=1` build
+    pub fn has_relapack(&self) -> bool {
+        self.symbols.iter().any(|sym| sym.starts_with("RELAPACK_"))
     }
 
     pub fn has_lapacke(&self) -> bool {
         for sym in &self.symbols {
-            if sym.starts_with("LAPACKE_") {
+            if let Some(true) = self.strip_affix(sym).map(|sym| sym.starts_with("LAPACKE_")) {
                 return true;
             }
         }
-        return false;
+        false
+    }
+
+    /// Check the library contains the runtime CPU dispatcher of `DYNAMIC_ARCH=1` build
+    pub fn has_dynamic_arch(&self) -> bool {
+        self.symbols
+            .iter()
+            .any(|sym| sym == "gotoblas_dynamic_init")
+    }
+
+    /// Check the library contains the thread server of threaded build, i.e. `USE_THREAD=1` or OpenMP
+    ///
+    /// Internal symbols like this are not renamed by the symbol prefix and suffix.
+    pub fn has_threading(&self) -> bool {
+        self.symbols.iter().any(|sym| sym == "blas_thread_init")
+    }
+
+    /// Check the library contains kernels for `target`
+    ///
+    /// `DYNAMIC_ARCH=1` build suffixes per-core kernels by its core name, e.g. `dgemm_kernel_HASWELL`.
+    pub fn has_kernels_for(&self, target: &Target) -> bool {
+        let suffix = format!("_{}", target);
+        self.symbols.iter().any(|sym| sym.ends_with(&suffix))
     }
 
     pub fn has_lib(&self, name: &str) -> bool {
@@ -202,7 +295,7 @@ impl LibInspect {
                 }
             };
         }
-        return false;
+        false
     }
 }
 
@@ -216,6 +309,42 @@ mod tests {
         assert!(path.exists());
         let detail = MakeConf::new(path).unwrap();
         assert!(!detail.no_fortran);
+        assert_eq!(detail.core, "HASWELL");
+        assert_eq!(detail.fc, "gfortran");
+        assert_eq!(detail.c_compiler, "GCC");
+        assert_eq!(detail.f_compiler, "GFORTRAN");
+    }
+
+    #[test]
+    fn symbol_affix() {
+        let lib = LibInspect {
+            path: PathBuf::from("libopenblas.a"),
+            libs: Vec::new(),
+            symbols: vec!["oblas_cblas_dgemm64".into(), "oblas_dsyev_64".into()],
+            symbol_prefix: String::new(),
+            symbol_suffix: String::new(),
+        };
+        assert!(!lib.has_cblas());
+        assert!(!lib.has_lapack());
+        let lib = lib.with_symbol_affix("oblas_", "64");
+        assert!(lib.has_cblas());
+        assert!(lib.has_lapack());
+        assert!(!lib.has_lapacke());
+        assert!(!lib.has_threading());
+
+        // ILP64 variant for side-by-side use with LP64
+        let lib = LibInspect {
+            symbols: vec![
+                "blas_thread_init".into(),
+                "cblas_dgemm64_".into(),
+                "dsyev_64_".into(),
+            ],
+            ..lib
+        }
+        .with_symbol_affix("", "64_");
+        assert!(lib.has_cblas());
+        assert!(lib.has_lapack());
+        assert!(lib.has_threading());
     }
 
     #[test]
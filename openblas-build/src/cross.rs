@@ -0,0 +1,224 @@
+//! Defaults for cross-compilation derived from Rust target triples
+
+use crate::{build::*, target::*};
+
+/// Rust target triple, e.g. `aarch64-unknown-linux-gnu`
+struct Triple<'a> {
+    arch: &'a str,
+    os: &'a str,
+    env: &'a str,
+}
+
+impl<'a> Triple<'a> {
+    fn parse(triple: &'a str) -> Self {
+        let parts: Vec<_> = triple.split('-').collect();
+        // The vendor is omitted in some triples, e.g. `aarch64-linux-android`
+        let (os, env) = match parts.len() {
+            0 | 1 => ("", ""),
+            2 => (parts[1], ""),
+            3 if parts[1] == "linux" => (parts[1], parts[2]),
+            3 => (parts[2], ""),
+            _ => (parts[2], parts[3]),
+        };
+        Triple {
+            arch: parts[0],
+            os,
+            env,
+        }
+    }
+
+    /// `target_arch` of Rust, e.g. `arm` for `armv7`
+    fn target_arch(&self) -> &'a str {
+        match self.arch {
+            "i386" | "i586" | "i686" => "x86",
+            arch if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+            arch if arch.starts_with("riscv64") => "riscv64",
+            arch if arch.starts_with("riscv32") => "riscv32",
+            "powerpc64le" => "powerpc64",
+            "mips64el" => "mips64",
+            "mipsel" => "mips",
+            "sparcv9" => "sparc64",
+            arch => arch,
+        }
+    }
+
+    fn is_32bit(&self) -> bool {
+        matches!(
+            self.target_arch(),
+            "x86" | "arm" | "mips" | "powerpc" | "riscv32" | "sparc"
+        )
+    }
+
+    fn is_x86(&self) -> bool {
+        matches!(self.target_arch(), "x86" | "x86_64")
+    }
+
+    /// Prefix of GNU cross toolchain, e.g. `aarch64-linux-gnu`
+    fn gnu_prefix(&self) -> Option<String> {
+        if self.os != "linux" || self.env.is_empty() {
+            return None;
+        }
+        let arch = match self.target_arch() {
+            "arm" => "arm",
+            "riscv64" => "riscv64",
+            _ => self.arch,
+        };
+        Some(format!("{}-linux-{}", arch, self.env))
+    }
+
+    /// Generic OpenBLAS target for the architecture,
+    /// since OpenBLAS cannot detect the CPU when cross-compiling.
+    fn generic_target(&self) -> Option<Target> {
+        Some(match self.target_arch() {
+            "x86" | "x86_64" => Target::SSE_GENERIC,
+            "aarch64" => Target::ARMV8,
+            "arm" if self.arch.starts_with("armv7") || self.arch.starts_with("thumbv7") => {
+                Target::ARMV7
+            }
+            "arm" if self.arch.starts_with("armv5") => Target::ARMV5,
+            "arm" => Target::ARMV6,
+            "powerpc64" => Target::POWER8,
+            "powerpc" => Target::PPCG4,
+            "mips64" => Target::MIPS64_GENERIC,
+            "riscv64" => Target::RISCV64_GENERIC,
+            "s390x" => Target::ZARCH_GENERIC,
+            "loongarch64" => Target::LOONGSONGENERIC,
+            _ => return None,
+        })
+    }
+}
+
+impl Configure {
+    /// Fill toolchain options not specified yet for cross-compilation
+    ///
+    /// Nothing is changed if `target_triple` and `host_triple` are same, i.e. not a cross build.
+    /// Otherwise, these options are filled if they are None:
+    ///
+    /// - `binary` by the pointer width of the target, e.g. [Binary::B32] for `i686-unknown-linux-gnu`
+    /// - `cc`, `fc` and `cross_suffix` by the GNU cross toolchain, e.g. `aarch64-linux-gnu-gcc`.
+    ///   Set `lapack_backend` to [LapackBackend::C] or `no_lapack` if the cross Fortran compiler is not installed,
+    ///   since OpenBLAS would otherwise compile LAPACK by the Fortran compiler of the host.
+    ///   If `c_compiler` or `f_compiler` is LLVM, it is used with `--target`, e.g. `clang --target=aarch64-linux-gnu`.
+    ///   These are not changed between x86 and x86_64, which are built by the host compilers.
+    /// - `hostcc` as `cc` to run helper executables, e.g. `getarch`, on the host
+    /// - `target` as a generic target of the architecture, e.g. [Target::ARMV8] for aarch64
+    ///
+    /// ```
+    /// use openblas_build::*;
+    /// let mut cfg = Configure::default();
+    /// cfg.fill_cross_defaults("aarch64-unknown-linux-gnu", "x86_64-unknown-linux-gnu");
+    /// assert_eq!(cfg.cc.as_deref(), Some("aarch64-linux-gnu-gcc"));
+    /// assert_eq!(cfg.fc.as_deref(), Some("aarch64-linux-gnu-gfortran"));
+    /// assert_eq!(cfg.target, Some(Target::ARMV8));
+    /// ```
+    pub fn fill_cross_defaults(&mut self, target_triple: &str, host_triple: &str) {
+        if target_triple == host_triple {
+            return;
+        }
+        let target = Triple::parse(target_triple);
+        let host = Triple::parse(host_triple);
+
+        if self.binary.is_none() {
+            self.binary = Some(if target.is_32bit() {
+                Binary::B32
+            } else {
+                Binary::B64
+            });
+        }
+        if target.is_x86() && host.is_x86() {
+            // e.g. i686 on x86_64 host, where `-m32` is used by OpenBLAS with `BINARY=32`
+            return;
+        }
+
+        if let Some(prefix) = target.gnu_prefix() {
+            if self.cc.is_none() {
+                self.cc = Some(match self.c_compiler {
+                    Some(CCompiler::Clang) => format!("clang --target={}", prefix),
+                    _ => format!("{}-gcc", prefix),
+                });
+            }
+            if self.fc.is_none() {
+                self.fc = Some(match self.f_compiler {
+                    Some(FortranCompiler::Flang) => format!("flang-new --target={}", prefix),
+                    _ => format!("{}-gfortran", prefix),
+                });
+            }
+            if self.cross_suffix.is_none() {
+                self.cross_suffix = Some(format!("{}-", prefix));
+            }
+        }
+        if self.hostcc.is_none() {
+            self.hostcc = Some("cc".into());
+        }
+        if self.target.is_none() {
+            self.target = target.generic_target();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    #[test]
+    fn native() {
+        let mut cfg = Configure::default();
+        cfg.fill_cross_defaults(HOST, HOST);
+        assert_eq!(cfg, Configure::default());
+    }
+
+    #[test]
+    fn i686() {
+        let mut cfg = Configure::default();
+        cfg.fill_cross_defaults("i686-unknown-linux-gnu", HOST);
+        assert_eq!(cfg.binary, Some(Binary::B32));
+        assert_eq!(cfg.cc, None);
+        assert_eq!(cfg.target, None);
+    }
+
+    #[test]
+    fn aarch64() {
+        let mut cfg = Configure::builder()
+            .target(Target::NEOVERSEN1)
+            .build()
+            .unwrap();
+        cfg.fill_cross_defaults("aarch64-unknown-linux-gnu", HOST);
+        assert_eq!(cfg.binary, Some(Binary::B64));
+        assert_eq!(cfg.cc.as_deref(), Some("aarch64-linux-gnu-gcc"));
+        assert_eq!(cfg.fc.as_deref(), Some("aarch64-linux-gnu-gfortran"));
+        assert_eq!(cfg.cross_suffix.as_deref(), Some("aarch64-linux-gnu-"));
+        assert_eq!(cfg.hostcc.as_deref(), Some("cc"));
+        assert_eq!(cfg.target, Some(Target::NEOVERSEN1));
+    }
+
+    #[test]
+    fn arm_and_riscv() {
+        let mut cfg = Configure::default();
+        cfg.fill_cross_defaults("armv7-unknown-linux-gnueabihf", HOST);
+        assert_eq!(cfg.binary, Some(Binary::B32));
+        assert_eq!(cfg.cc.as_deref(), Some("arm-linux-gnueabihf-gcc"));
+        assert_eq!(cfg.target, Some(Target::ARMV7));
+
+        let mut cfg = Configure::default();
+        cfg.fill_cross_defaults("riscv64gc-unknown-linux-gnu", HOST);
+        assert_eq!(cfg.cc.as_deref(), Some("riscv64-linux-gnu-gcc"));
+        assert_eq!(cfg.target, Some(Target::RISCV64_GENERIC));
+    }
+
+    #[test]
+    fn llvm() {
+        let mut cfg = Configure::builder()
+            .c_compiler(CCompiler::Clang)
+            .f_compiler(FortranCompiler::Flang)
+            .build()
+            .unwrap();
+        cfg.fill_cross_defaults("aarch64-unknown-linux-gnu", HOST);
+        assert_eq!(cfg.cc.as_deref(), Some("clang --target=aarch64-linux-gnu"));
+        assert_eq!(
+            cfg.fc.as_deref(),
+            Some("flang-new --target=aarch64-linux-gnu")
+        );
+    }
+}
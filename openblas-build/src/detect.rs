@@ -0,0 +1,521 @@
+//! Detect CPU target of the host
+
+use crate::{error::*, target::*};
+use std::{env, fs};
+
+/// Host CPU detected from `/proc/cpuinfo`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostCpu {
+    /// Target which OpenBLAS `getarch` would choose on this host
+    pub target: Target,
+    /// CPU features (or identifiers for non-x86 CPUs) used to determine `target`,
+    /// e.g. `["avx2", "fma"]` for `HASWELL`
+    pub features: Vec<String>,
+}
+
+impl Target {
+    /// Detect the target of the host CPU
+    ///
+    /// This does not run `getarch` of OpenBLAS, but emulates its decision using `/proc/cpuinfo`.
+    /// It is useful to know (and pin) the target before running long `make`:
+    ///
+    /// ```no_run
+    /// use openblas_build::*;
+    /// let host = Target::detect_host().unwrap();
+    /// println!("Build for {} (using {:?})", host.target, host.features);
+    /// let mut cfg = Configure::default();
+    /// cfg.target = Some(host.target);
+    /// ```
+    pub fn detect_host() -> Result<HostCpu, Error> {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo")?;
+        HostCpu::from_cpuinfo(env::consts::ARCH, &cpuinfo)
+    }
+
+    /// Derive the target from rustc's `target-cpu` and target features
+    ///
+    /// `target_cpu` is LLVM's CPU name e.g. `"skylake-avx512"` given by `-C target-cpu`,
+    /// and `target_features` are names in `CARGO_CFG_TARGET_FEATURE` e.g. `"avx512f"`.
+    /// `target_cpu` has priority, and features are used if it is absent or generic.
+    ///
+    /// Returns `None` if no specific target is found,
+    /// i.e. OpenBLAS should detect the target by itself.
+    ///
+    /// ```
+    /// use openblas_build::*;
+    /// assert_eq!(Target::from_rustc("x86_64", Some("skylake-avx512"), &[]), Some(Target::SKYLAKEX));
+    /// assert_eq!(Target::from_rustc("x86_64", None, &["avx", "avx2", "fma"]), Some(Target::HASWELL));
+    /// assert_eq!(Target::from_rustc("x86_64", None, &["sse", "sse2"]), None);
+    /// ```
+    pub fn from_rustc(
+        target_arch: &str,
+        target_cpu: Option<&str>,
+        target_features: &[&str],
+    ) -> Option<Target> {
+        if target_cpu == Some("native") {
+            return Target::detect_host().ok().map(|host| host.target);
+        }
+        if let Some(target) = target_cpu.and_then(|cpu| from_llvm_cpu(target_arch, cpu)) {
+            return Some(target);
+        }
+        let has = |f: &str| target_features.contains(&f);
+        match target_arch {
+            "x86" | "x86_64" => {
+                if has("avx512bf16") {
+                    Some(Target::COOPERLAKE)
+                } else if has("avx512f") {
+                    Some(Target::SKYLAKEX)
+                } else if has("avx2") && has("fma") {
+                    Some(Target::HASWELL)
+                } else if has("avx") {
+                    Some(Target::SANDYBRIDGE)
+                } else if has("sse4.2") {
+                    Some(Target::NEHALEM)
+                } else {
+                    None
+                }
+            }
+            "aarch64" if has("sve") => Some(Target::ARMV8SVE),
+            "riscv64" if has("v") => Some(Target::RISCV64_ZVL128B),
+            _ => None,
+        }
+    }
+
+    /// Derive the target from the environment variables which cargo sets for build.rs
+    ///
+    /// - `CARGO_CFG_TARGET_ARCH`
+    /// - `CARGO_ENCODED_RUSTFLAGS` for `-C target-cpu`
+    /// - `CARGO_CFG_TARGET_FEATURE`
+    ///
+    /// See [Target::from_rustc] for detail.
+    pub fn from_cargo_env() -> Option<Target> {
+        let target_arch = env::var("CARGO_CFG_TARGET_ARCH").ok()?;
+        let rustflags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+        let target_cpu = target_cpu_from_rustflags(&rustflags);
+        let features = env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default();
+        let features: Vec<_> = features.split(',').collect();
+        Target::from_rustc(&target_arch, target_cpu.as_deref(), &features)
+    }
+}
+
+/// Find the last `-C target-cpu` in `CARGO_ENCODED_RUSTFLAGS`, whose flags are separated by `0x1f`
+fn target_cpu_from_rustflags(rustflags: &str) -> Option<String> {
+    let mut target_cpu = None;
+    let mut flags = rustflags.split('\x1f');
+    while let Some(flag) = flags.next() {
+        let codegen = match flag {
+            "-C" | "--codegen" => flags.next().unwrap_or(""),
+            _ => flag
+                .strip_prefix("-C")
+                .or_else(|| flag.strip_prefix("--codegen="))
+                .unwrap_or(""),
+        };
+        if let Some(cpu) = codegen.trim().strip_prefix("target-cpu=") {
+            target_cpu = Some(cpu.to_string());
+        }
+    }
+    target_cpu
+}
+
+/// Map LLVM's CPU name to the target
+fn from_llvm_cpu(target_arch: &str, cpu: &str) -> Option<Target> {
+    let target = match target_arch {
+        "x86" | "x86_64" => match cpu {
+            "sapphirerapids" | "emeraldrapids" | "graniterapids" | "graniterapids-d" => {
+                Target::SAPPHIRERAPIDS
+            }
+            "cooperlake" | "znver4" | "znver5" => Target::COOPERLAKE,
+            "skylake-avx512" | "cascadelake" | "cannonlake" | "icelake-client"
+            | "icelake-server" | "tigerlake" | "rocketlake" | "x86-64-v4" => Target::SKYLAKEX,
+            "haswell" | "broadwell" | "skylake" | "alderlake" | "raptorlake" | "meteorlake"
+            | "x86-64-v3" => Target::HASWELL,
+            "znver1" | "znver2" | "znver3" => Target::ZEN,
+            "sandybridge" | "ivybridge" | "corei7-avx" | "core-avx-i" => Target::SANDYBRIDGE,
+            "nehalem" | "westmere" | "corei7" | "x86-64-v2" => Target::NEHALEM,
+            "penryn" => Target::PENRYN,
+            "core2" => Target::CORE2,
+            "atom" | "bonnell" | "silvermont" | "slm" | "goldmont" | "goldmont-plus"
+            | "tremont" => Target::ATOM,
+            "bdver1" => Target::BULLDOZER,
+            "bdver2" => Target::PILEDRIVER,
+            "bdver3" => Target::STEAMROLLER,
+            "bdver4" => Target::EXCAVATOR,
+            "btver1" | "btver2" => Target::BOBCAT,
+            "amdfam10" | "barcelona" => Target::BARCELONA,
+            "prescott" | "nocona" => Target::PRESCOTT,
+            _ => return None,
+        },
+        "aarch64" => match cpu {
+            "cortex-a53" => Target::CORTEXA53,
+            "cortex-a55" => Target::CORTEXA55,
+            "cortex-a57" => Target::CORTEXA57,
+            "cortex-a72" => Target::CORTEXA72,
+            "cortex-a73" => Target::CORTEXA73,
+            "cortex-a76" => Target::CORTEXA76,
+            "cortex-a510" => Target::CORTEXA510,
+            "cortex-a710" => Target::CORTEXA710,
+            "cortex-x1" => Target::CORTEXX1,
+            "cortex-x2" => Target::CORTEXX2,
+            "neoverse-n1" => Target::NEOVERSEN1,
+            "neoverse-v1" => Target::NEOVERSEV1,
+            "neoverse-n2" => Target::NEOVERSEN2,
+            "a64fx" => Target::A64FX,
+            "thunderx" => Target::THUNDERX,
+            "thunderx2t99" => Target::THUNDERX2T99,
+            "thunderx3t110" => Target::THUNDERX3T110,
+            "tsv110" => Target::TSV110,
+            "falkor" => Target::FALKOR,
+            _ if cpu.starts_with("apple-") => Target::VORTEX,
+            _ => return None,
+        },
+        "powerpc64" => match cpu {
+            "pwr8" | "power8" => Target::POWER8,
+            "pwr9" | "power9" => Target::POWER9,
+            "pwr10" | "power10" => Target::POWER10,
+            _ => return None,
+        },
+        "s390x" => match cpu {
+            "z13" | "arch11" => Target::Z13,
+            "z14" | "arch12" => Target::Z14,
+            "z15" | "arch13" | "z16" | "arch14" => Target::Z15,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(target)
+}
+
+impl HostCpu {
+    /// Determine the target from the content of `/proc/cpuinfo`
+    ///
+    /// `arch` is an architecture name in the manner of [std::env::consts::ARCH], e.g. `"x86_64"`.
+    pub fn from_cpuinfo(arch: &str, cpuinfo: &str) -> Result<Self, Error> {
+        let info = CpuInfo::parse(cpuinfo);
+        let detected = match arch {
+            "x86" | "x86_64" => Some(detect_x86(&info)),
+            "aarch64" => Some(detect_aarch64(&info)),
+            "powerpc64" => detect_power(&info),
+            "s390x" => Some(detect_zarch(cpuinfo)),
+            "riscv64" => Some(detect_riscv64(&info)),
+            "loongarch64" => Some(detect_loongarch64(&info)),
+            _ => None,
+        };
+        detected.ok_or_else(|| Error::HostCpuNotDetected { arch: arch.into() })
+    }
+}
+
+/// Entries of the first processor in `/proc/cpuinfo`
+struct CpuInfo {
+    entries: Vec<(String, String)>,
+}
+
+impl CpuInfo {
+    fn parse(cpuinfo: &str) -> Self {
+        let mut entries = Vec::new();
+        for line in cpuinfo.lines() {
+            let mut kv = line.splitn(2, ':');
+            let key = kv.next().unwrap_or("").trim();
+            let value = kv.next().unwrap_or("").trim();
+            // Same entries are repeated for each processors
+            if key.is_empty() || entries.iter().any(|(k, _)| k == key) {
+                continue;
+            }
+            entries.push((key.to_string(), value.to_string()));
+        }
+        CpuInfo { entries }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parse decimal or hexadecimal (`0x` prefixed) value
+    fn get_num(&self, key: &str) -> Option<u32> {
+        let value = self.get(key)?;
+        if let Some(hex) = value.strip_prefix("0x") {
+            u32::from_str_radix(hex, 16).ok()
+        } else {
+            value.parse().ok()
+        }
+    }
+
+    fn flags(&self) -> Vec<&str> {
+        self.get("flags")
+            .or_else(|| self.get("Features"))
+            .unwrap_or("")
+            .split_whitespace()
+            .collect()
+    }
+}
+
+fn host_cpu(target: Target, features: &[&str]) -> HostCpu {
+    HostCpu {
+        target,
+        features: features.iter().map(|f| f.to_string()).collect(),
+    }
+}
+
+/// Emulates `cpuid_x86.c`, using the CPU family for AMD and the instruction sets otherwise
+fn detect_x86(info: &CpuInfo) -> HostCpu {
+    let flags = info.flags();
+    let has = |required: &[&str]| required.iter().all(|f| flags.contains(f));
+    let by_features = |candidates: &[(Target, &[&str])]| {
+        candidates
+            .iter()
+            .find(|(_, required)| has(required))
+            .map(|(target, required)| host_cpu(*target, required))
+    };
+
+    const AVX512: &[(Target, &[&str])] = &[
+        (Target::SAPPHIRERAPIDS, &["avx512_bf16", "amx_bf16"]),
+        (Target::COOPERLAKE, &["avx512f", "avx512_bf16"]),
+        (Target::SKYLAKEX, &["avx512f", "avx512bw"]),
+    ];
+    const LEGACY: &[(Target, &[&str])] = &[
+        (Target::HASWELL, &["avx2", "fma"]),
+        (Target::SANDYBRIDGE, &["avx"]),
+        (Target::NEHALEM, &["sse4_2"]),
+        (Target::PENRYN, &["sse4_1"]),
+        (Target::CORE2, &["ssse3"]),
+        (Target::PRESCOTT, &["pni"]),
+        (Target::NORTHWOOD, &["sse2"]),
+    ];
+
+    let vendor = info.get("vendor_id").unwrap_or("");
+    let family = info.get_num("cpu family").unwrap_or(0);
+    let model = info.get_num("model").unwrap_or(0);
+    if vendor == "AuthenticAMD" || vendor == "HygonGenuine" {
+        let amd = match family {
+            // Zen4 and later, which have AVX-512, use Intel kernels
+            0x17..=0xff => by_features(AVX512).or_else(|| Some(host_cpu(Target::ZEN, &["avx2"]))),
+            0x15 => Some(match model {
+                0x02 | 0x10..=0x1f => host_cpu(Target::PILEDRIVER, &["avx", "fma"]),
+                0x30..=0x3f => host_cpu(Target::STEAMROLLER, &["avx", "fma"]),
+                0x60..=0x7f => host_cpu(Target::EXCAVATOR, &["avx2", "fma"]),
+                _ => host_cpu(Target::BULLDOZER, &["avx"]),
+            }),
+            0x14 => Some(host_cpu(Target::BOBCAT, &[])),
+            0x10 | 0x12 | 0x16 => Some(host_cpu(Target::BARCELONA, &[])),
+            0x0f | 0x11 => by_features(&[(Target::OPTERON_SSE3, &["pni"])])
+                .or_else(|| Some(host_cpu(Target::OPTERON, &[]))),
+            _ => None,
+        };
+        if let Some(amd) = amd {
+            return amd;
+        }
+    }
+    by_features(AVX512)
+        .or_else(|| by_features(LEGACY))
+        .unwrap_or_else(|| host_cpu(Target::SSE_GENERIC, &[]))
+}
+
+/// Emulates `cpuid_arm64.c`, using `CPU implementer` and `CPU part`
+fn detect_aarch64(info: &CpuInfo) -> HostCpu {
+    let implementer = info.get_num("CPU implementer").unwrap_or(0);
+    let part = info.get_num("CPU part").unwrap_or(0);
+    let target = match (implementer, part) {
+        // ARM
+        (0x41, 0xd03) => Some(Target::CORTEXA53),
+        (0x41, 0xd05) => Some(Target::CORTEXA55),
+        (0x41, 0xd07) => Some(Target::CORTEXA57),
+        (0x41, 0xd08) => Some(Target::CORTEXA72),
+        (0x41, 0xd09) => Some(Target::CORTEXA73),
+        (0x41, 0xd0b) => Some(Target::CORTEXA76),
+        (0x41, 0xd0c) => Some(Target::NEOVERSEN1),
+        (0x41, 0xd40) => Some(Target::NEOVERSEV1),
+        (0x41, 0xd44) => Some(Target::CORTEXX1),
+        (0x41, 0xd46) => Some(Target::CORTEXA510),
+        (0x41, 0xd47) => Some(Target::CORTEXA710),
+        (0x41, 0xd48) => Some(Target::CORTEXX2),
+        (0x41, 0xd49) => Some(Target::NEOVERSEN2),
+        // Cavium
+        (0x43, 0x0a1) => Some(Target::THUNDERX),
+        (0x43, 0x0af) => Some(Target::THUNDERX2T99),
+        (0x43, 0x0b8) => Some(Target::THUNDERX3T110),
+        // Fujitsu
+        (0x46, 0x001) => Some(Target::A64FX),
+        // HiSilicon
+        (0x48, 0xd01) => Some(Target::TSV110),
+        // Ampere (formerly APM)
+        (0x50, 0x000) => Some(Target::EMAG8180),
+        // Qualcomm
+        (0x51, 0xc00) => Some(Target::FALKOR),
+        // Apple
+        (0x61, _) => Some(Target::VORTEX),
+        // Phytium
+        (0x70, 0x660..=0x663) => Some(Target::FT2000),
+        _ => None,
+    };
+    if let Some(target) = target {
+        return HostCpu {
+            target,
+            features: vec![
+                format!("CPU implementer 0x{:02x}", implementer),
+                format!("CPU part 0x{:03x}", part),
+            ],
+        };
+    }
+    if info.flags().contains(&"sve") {
+        host_cpu(Target::ARMV8SVE, &["sve"])
+    } else {
+        host_cpu(Target::ARMV8, &[])
+    }
+}
+
+/// Emulates `cpuid_power.c`, using `cpu` entry e.g. `POWER9 (raw), altivec supported`
+fn detect_power(info: &CpuInfo) -> Option<HostCpu> {
+    let cpu = info.get("cpu")?;
+    let target = [Target::POWER10, Target::POWER9, Target::POWER8]
+        .iter()
+        .find(|target| cpu.starts_with(target.name()))
+        .copied()?;
+    Some(host_cpu(target, &[cpu.split_whitespace().next()?]))
+}
+
+/// Emulates `cpuid_zarch.c`, using machine type e.g. `processor 0: ... machine = 3906`
+fn detect_zarch(cpuinfo: &str) -> HostCpu {
+    let machine = cpuinfo
+        .split("machine = ")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|machine| machine.parse::<u32>().ok())
+        .unwrap_or(0);
+    let target = match machine {
+        2964 | 2965 => Target::Z13,
+        3906 | 3907 => Target::Z14,
+        8561.. => Target::Z15,
+        _ => return host_cpu(Target::ZARCH_GENERIC, &[]),
+    };
+    HostCpu {
+        target,
+        features: vec![format!("machine {}", machine)],
+    }
+}
+
+/// Use `isa` entry e.g. `rv64imafdcv`
+fn detect_riscv64(info: &CpuInfo) -> HostCpu {
+    let isa = info.get("isa").unwrap_or("");
+    // Single-letter extensions follows `rv64`, and multi-letter ones follows `_`
+    let base = isa.split('_').next().unwrap_or("");
+    if base.starts_with("rv64") && base[4..].contains('v') {
+        host_cpu(Target::RISCV64_ZVL128B, &["v"])
+    } else {
+        host_cpu(Target::RISCV64_GENERIC, &[])
+    }
+}
+
+/// Emulates `cpuid_loongarch64.c`, using `Model Name` e.g. `Loongson-3A5000`
+fn detect_loongarch64(info: &CpuInfo) -> HostCpu {
+    let model = info.get("Model Name").unwrap_or("");
+    if ["3A5000", "3C5000", "3D5000", "3A6000"]
+        .iter()
+        .any(|m| model.contains(m))
+    {
+        host_cpu(Target::LOONGSON3R5, &[model])
+    } else if model.contains("2K1000") {
+        host_cpu(Target::LOONGSON2K1000, &[model])
+    } else {
+        host_cpu(Target::LOONGSONGENERIC, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_intel_haswell() {
+        let cpuinfo = r#"
+processor	: 0
+vendor_id	: GenuineIntel
+cpu family	: 6
+model		: 60
+model name	: Intel(R) Core(TM) i7-4770 CPU @ 3.40GHz
+flags		: fpu sse sse2 pni ssse3 fma sse4_1 sse4_2 avx avx2
+
+processor	: 1
+vendor_id	: GenuineIntel
+"#;
+        let host = HostCpu::from_cpuinfo("x86_64", cpuinfo).unwrap();
+        assert_eq!(host.target, Target::HASWELL);
+        assert_eq!(host.features, vec!["avx2", "fma"]);
+    }
+
+    #[test]
+    fn detect_amd_zen() {
+        let zen3 = r#"
+vendor_id	: AuthenticAMD
+cpu family	: 25
+model		: 33
+flags		: fpu sse sse2 pni ssse3 fma sse4_1 sse4_2 avx avx2
+"#;
+        let host = HostCpu::from_cpuinfo("x86_64", zen3).unwrap();
+        assert_eq!(host.target, Target::ZEN);
+
+        let zen4 = r#"
+vendor_id	: AuthenticAMD
+cpu family	: 25
+model		: 97
+flags		: fpu sse sse2 avx avx2 avx512f avx512bw avx512_bf16
+"#;
+        let host = HostCpu::from_cpuinfo("x86_64", zen4).unwrap();
+        assert_eq!(host.target, Target::COOPERLAKE);
+    }
+
+    #[test]
+    fn detect_neoverse_n1() {
+        let cpuinfo = r#"
+processor	: 0
+BogoMIPS	: 243.75
+Features	: fp asimd evtstrm aes pmull sha1 sha2 crc32 atomics
+CPU implementer	: 0x41
+CPU architecture: 8
+CPU variant	: 0x3
+CPU part	: 0xd0c
+"#;
+        let host = HostCpu::from_cpuinfo("aarch64", cpuinfo).unwrap();
+        assert_eq!(host.target, Target::NEOVERSEN1);
+    }
+
+    #[test]
+    fn target_cpu_in_rustflags() {
+        assert_eq!(
+            target_cpu_from_rustflags("-Ctarget-cpu=haswell\x1f-Copt-level=3"),
+            Some("haswell".into())
+        );
+        assert_eq!(
+            target_cpu_from_rustflags("-C\x1ftarget-cpu=haswell\x1f-C\x1ftarget-cpu=znver3"),
+            Some("znver3".into())
+        );
+        assert_eq!(target_cpu_from_rustflags("--codegen=opt-level=3"), None);
+        assert_eq!(target_cpu_from_rustflags(""), None);
+    }
+
+    #[test]
+    fn rustc_target_cpu() {
+        assert_eq!(
+            Target::from_rustc("x86_64", Some("znver4"), &["avx512f"]),
+            Some(Target::COOPERLAKE)
+        );
+        // Generic CPU name falls back to target features
+        assert_eq!(
+            Target::from_rustc("x86_64", Some("x86-64"), &["avx512f"]),
+            Some(Target::SKYLAKEX)
+        );
+        assert_eq!(
+            Target::from_rustc("aarch64", Some("neoverse-v1"), &[]),
+            Some(Target::NEOVERSEV1)
+        );
+        assert_eq!(
+            Target::from_rustc("aarch64", Some("generic"), &["neon"]),
+            None
+        );
+    }
+
+    #[test]
+    fn detect_unknown_arch() {
+        assert!(HostCpu::from_cpuinfo("wasm32", "").is_err());
+    }
+}
@@ -1,8 +1,12 @@
+use crate::{build::Precision, target::Target};
 use std::{io, path::*, process::Command};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
+    #[error(transparent)]
+    InvalidConfigure(#[from] ConfigureError),
+
     #[error("Subprocess returns with non-zero status: {status}")]
     NonZeroExitStatus { status: i32 },
 
@@ -12,6 +16,15 @@ pub enum Error {
     #[error("Fortran compiler not found. It is necessary to build LAPACK.")]
     FortranCompilerNotFound,
 
+    #[error("LAPACK symbols are not found in the library")]
+    LapackNotFound,
+
+    #[error("External LAPACK library does not contain LAPACK symbols: {}", path.display())]
+    ExternalLapackInvalid { path: PathBuf },
+
+    #[error("Library is not built with BUILD_RELAPACK=1")]
+    RelapackNotBuilt,
+
     #[error("Cannot canonicalize path in Linker flag: {}", path.display())]
     CannotCanonicalizePath { path: PathBuf },
 
@@ -21,6 +34,48 @@ pub enum Error {
     #[error("Library file does not exist: {}", path.display())]
     LibraryNotExist { path: PathBuf },
 
+    #[error("File is not installed: {}", path.display())]
+    InstalledFileNotExist { path: PathBuf },
+
+    #[error("Invalid TOML: {0}")]
+    InvalidToml(#[from] toml::de::Error),
+
+    #[error("Cannot serialize into TOML: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+
+    #[error("Invalid value of environment variable {name}: {value}")]
+    InvalidEnvVar { name: String, value: String },
+
+    #[error("Unknown OpenBLAS target: {name}")]
+    UnknownTarget { name: String },
+
+    #[error("TargetList.txt does not exist: {}", path.display())]
+    TargetListNotExist { path: PathBuf },
+
+    #[error("Target {target} cannot be built for target_arch={target_arch}")]
+    TargetArchMismatch { target: Target, target_arch: String },
+
+    #[error("Cannot detect the host CPU target on {arch}")]
+    HostCpuNotDetected { arch: String },
+
+    #[error("{} precision is {} the library", precision.name(), if *expected { "not found in" } else { "unexpectedly built into" })]
+    PrecisionMismatch {
+        precision: Precision,
+        expected: bool,
+    },
+
+    #[error("Library is not built with DYNAMIC_ARCH=1")]
+    DynamicArchNotBuilt,
+
+    #[error("Kernels for {target} are not found in the library built with DYNAMIC_ARCH=1")]
+    DynamicKernelNotFound { target: Target },
+
+    #[error("Build record does not exist: {}", path.display())]
+    BuildRecordNotExist { path: PathBuf },
+
+    #[error("Existing build differs from the configuration in: {}", fields.join(", "))]
+    ConfigureMismatch { fields: Vec<String> },
+
     #[error("Other IO errors: {0:?}")]
     IOError(#[from] io::Error),
 }
@@ -45,3 +100,57 @@ impl CheckCall for Command {
         }
     }
 }
+
+/// Contradictory or meaningless combination of options in [crate::Configure]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum ConfigureError {
+    #[error("Both static and shared libraries are disabled")]
+    NoLibrary,
+
+    #[error("use_openmp requires use_thread")]
+    OpenMPWithoutThread,
+
+    #[error("ILP64 interface requires 64-bit binary")]
+    Ilp64On32Bit,
+
+    #[error("{option} = {value} is out of range [{min}, {max}]")]
+    OutOfRange {
+        option: &'static str,
+        value: usize,
+        min: usize,
+        max: usize,
+    },
+
+    #[error("{option} must be positive")]
+    ZeroThreads { option: &'static str },
+
+    #[error(
+        "use_locking is meaningless with use_thread, since threaded OpenBLAS is always thread-safe"
+    )]
+    LockingWithThread,
+
+    #[error("{option} is meaningless without use_thread")]
+    RequiresThread { option: &'static str },
+
+    #[error("AddressSanitizer and ThreadSanitizer cannot be used together")]
+    IncompatibleSanitizers,
+
+    #[error("LAPACKE cannot be built without LAPACK. Set no_lapacke too.")]
+    LapackeWithoutLapack,
+
+    #[error("external_lapack requires no_lapack")]
+    ExternalLapackWithLapack,
+
+    #[error("external_lapack cannot be used with symbol_prefix or symbol_suffix")]
+    ExternalLapackWithSymbolAffix,
+
+    #[error("lapack_backend is meaningless with no_lapack")]
+    LapackBackendWithoutLapack,
+
+    #[error("make goal `{goal}` is required to build the libraries")]
+    MissingMakeGoal { goal: &'static str },
+
+    #[error("{option} is meaningless without dynamic_arch")]
+    RequiresDynamicArch { option: &'static str },
+}
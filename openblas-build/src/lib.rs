@@ -13,7 +13,22 @@
 //! [OpenBLAS]: https://github.com/xianyi/OpenBLAS
 
 mod build;
+mod builder;
+mod cache;
+mod cc_env;
 mod check;
+mod cross;
+mod detect;
 pub mod error;
+mod manifest;
+mod mode;
+mod stage;
+mod target;
+mod tuning;
 pub use build::*;
+pub use builder::*;
 pub use check::*;
+pub use detect::*;
+pub use mode::*;
+pub use target::*;
+pub use tuning::*;
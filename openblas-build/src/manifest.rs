@@ -0,0 +1,335 @@
+//! Save and load [Configure], and write [Deliverables] as a manifest, in TOML
+
+use crate::{build::*, check::*, error::*};
+use serde::{Serialize, Serializer};
+use std::{collections::BTreeSet, fs, path::*};
+use toml::{Table, Value};
+
+impl Configure {
+    /// Serialize into TOML
+    ///
+    /// ```
+    /// use openblas_build::*;
+    /// let cfg = Configure::builder().target(Target::HASWELL).build().unwrap();
+    /// let toml = cfg.to_toml().unwrap();
+    /// assert!(toml.contains(r#"target = "HASWELL""#));
+    /// assert_eq!(Configure::from_toml(&toml).unwrap(), cfg);
+    /// ```
+    pub fn to_toml(&self) -> Result<String, Error> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Deserialize from TOML, and validate it
+    ///
+    /// Options missing in TOML are same as [Configure::default].
+    pub fn from_toml(input: &str) -> Result<Self, Error> {
+        let cfg: Configure = toml::from_str(input)?;
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    /// Save as a TOML file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    /// Load from a TOML file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+}
+
+/// File name of the build record written in `out_dir` by [Configure::build]
+pub(crate) const BUILD_RECORD: &str = "openblas-configure.toml";
+
+/// Options which do not change the deliverables, and are ignored in the build record and the cache key
+const NON_BUILD_OPTIONS: &[&str] = &["goals", "install_prefix", "jobs"];
+
+impl Configure {
+    /// Write the build record into `out_dir`
+    pub(crate) fn write_record(&self, out_dir: &Path) -> Result<(), Error> {
+        self.save(out_dir.join(BUILD_RECORD))
+    }
+
+    /// All options as TOML table without [NON_BUILD_OPTIONS], which identifies the deliverables
+    pub(crate) fn to_build_table(&self) -> Result<Table, Error> {
+        let mut table = Table::try_from(self)?;
+        for key in NON_BUILD_OPTIONS {
+            table.remove(*key);
+        }
+        Ok(table)
+    }
+
+    /// Compare the build record in `out_dir` with `self`
+    ///
+    /// Returns [Error::ConfigureMismatch] with the names of differing options,
+    /// e.g. `tuning.buffersize` for options in a table.
+    pub(crate) fn check_record(&self, out_dir: &Path) -> Result<(), Error> {
+        let path = out_dir.join(BUILD_RECORD);
+        if !path.exists() {
+            return Err(Error::BuildRecordNotExist { path });
+        }
+        // Normalize through Configure to fill omitted options with default
+        let record: Configure = toml::from_str(&fs::read_to_string(&path)?)?;
+        let mut fields = Vec::new();
+        diff_tables(
+            "",
+            &self.to_build_table()?,
+            &record.to_build_table()?,
+            &mut fields,
+        );
+        if fields.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ConfigureMismatch { fields })
+        }
+    }
+}
+
+/// Push the dotted keys of values differing between `a` and `b` into `fields`
+fn diff_tables(prefix: &str, a: &Table, b: &Table, fields: &mut Vec<String>) {
+    let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    for key in keys {
+        let name = format!("{}{}", prefix, key);
+        match (a.get(key), b.get(key)) {
+            (Some(Value::Table(a)), Some(Value::Table(b))) => {
+                diff_tables(&format!("{}.", name), a, b, fields)
+            }
+            (a, b) if a != b => fields.push(name),
+            _ => {}
+        }
+    }
+}
+
+/// Summary of [LibInspect] in the manifest
+#[derive(Serialize)]
+struct LibSummary<'a> {
+    path: &'a Path,
+    needed: &'a [String],
+    symbols: usize,
+    has_cblas: bool,
+    has_lapack: bool,
+    has_lapacke: bool,
+    has_relapack: bool,
+}
+
+/// Symbols are summarized into their number and what they include
+impl Serialize for LibInspect {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LibSummary {
+            path: self.path(),
+            needed: &self.libs,
+            symbols: self.symbols.len(),
+            has_cblas: self.has_cblas(),
+            has_lapack: self.has_lapack(),
+            has_lapacke: self.has_lapacke(),
+            has_relapack: self.has_relapack(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Deliverables {
+    /// Manifest of what was actually built, in TOML
+    ///
+    /// ```toml
+    /// openblas_version = "0.3.10"
+    /// lib_dir = "/path/to/prefix/lib"
+    /// include_dir = "/path/to/prefix/include"
+    ///
+    /// [static_lib]
+    /// path = "/path/to/libopenblas.a"
+    /// needed = []
+    /// symbols = 9457
+    /// has_cblas = true
+    /// # ...
+    ///
+    /// [make_conf]
+    /// os_name = "Linux"
+    /// core = "HASWELL"
+    /// # ...
+    ///
+    /// [make_conf.c_extra_libs]
+    /// search_paths = []
+    /// libs = ["m"]
+    ///
+    /// [compilers]
+    /// cc = "cc"
+    /// cc_version = "cc (GCC) 10.2.0"
+    /// # ...
+    ///
+    /// [tuning]
+    /// hugetlb_allocation = false
+    /// # ...
+    /// ```
+    pub fn to_manifest(&self) -> Result<String, Error> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Write [Deliverables::to_manifest] into a file
+    pub fn write_manifest(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        fs::write(path, self.to_manifest()?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mode::*, target::*, tuning::*};
+
+    #[test]
+    fn configure_round_trip() {
+        let tuning = Tuning {
+            small_matrix_opt: Some(false),
+            gemm_multithread_threshold: Some(8),
+            buffersize: Some(22),
+            hugetlb_allocation: true,
+            ..Default::default()
+        };
+        let mode = BuildMode {
+            debug: true,
+            sanitizers: vec![Sanitizer::Address, Sanitizer::Undefined],
+            ..Default::default()
+        };
+        let cfg = Configure::builder()
+            .use_thread(true)
+            .use_openmp(true)
+            .num_threads(128)
+            .num_parallel(2)
+            .no_affinity(false)
+            .use_tls(true)
+            .precisions(vec![Precision::Double, Precision::Complex16])
+            .gc_sections(true)
+            .lapack_backend(LapackBackend::ReLapack)
+            .dynamic_arch(true)
+            .dynamic_list(vec![Target::HASWELL, Target::Custom("NEWCPU")])
+            .interface(Interface::ILP64)
+            .cc("clang --target=x86_64-linux-gnu")
+            .c_compiler(CCompiler::Clang)
+            .f_compiler(FortranCompiler::Flang)
+            .common_opt(vec!["-march=native"])
+            .fcommon_opt(vec!["-fno-optimize-sibling-calls"])
+            .ldflags(vec!["-fuse-ld=lld"])
+            .ar("llvm-ar")
+            .cflags(vec!["-O2", "-g"])
+            .binary(Binary::B64)
+            .cross_suffix("x86_64-linux-gnu-")
+            .sysroot("/usr/x86_64-linux-gnu")
+            .tuning(tuning)
+            .mode(mode)
+            .symbol_prefix("oblas_")
+            .extra_args(vec!["NUM_THREADS=8"])
+            .goals(vec![MakeGoal::Libs, MakeGoal::Netlib, MakeGoal::Tests])
+            .no_shared(true)
+            .install_prefix("/opt/openblas")
+            .jobs(4)
+            .build()
+            .unwrap();
+        assert_eq!(Configure::from_toml(&cfg.to_toml().unwrap()).unwrap(), cfg);
+    }
+
+    #[test]
+    fn check_record() {
+        let out_dir = std::env::temp_dir().join(format!(
+            "openblas-build-check-record-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&out_dir).unwrap();
+
+        let cfg = Configure::builder()
+            .target(Target::HASWELL)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            cfg.check_record(&out_dir),
+            Err(Error::BuildRecordNotExist { .. })
+        ));
+        cfg.write_record(&out_dir).unwrap();
+        assert!(cfg.check_record(&out_dir).is_ok());
+        // Goals and parallelism of make are not a part of the record
+        let mut parallel = cfg.clone();
+        parallel.jobs = Some(8);
+        parallel.goals.push(MakeGoal::Tests);
+        parallel.install_prefix = Some(out_dir.join("prefix"));
+        assert!(parallel.check_record(&out_dir).is_ok());
+
+        let other = Configure::builder()
+            .use_thread(true)
+            .interface(Interface::ILP64)
+            .build()
+            .unwrap();
+        match other.check_record(&out_dir) {
+            Err(Error::ConfigureMismatch { fields }) => {
+                assert_eq!(fields, vec!["interface", "target", "use_thread"])
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn configure_from_toml() {
+        let cfg = Configure::from_toml(
+            r#"
+precisions = [
+    "double",
+    "complex16", # trailing comma
+]
+binary = 64
+f_compiler = "flang-new"
+
+[tuning]
+buffersize = 25
+
+[mode]
+sanitizers = ["address"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.precisions,
+            vec![Precision::Double, Precision::Complex16]
+        );
+        assert_eq!(cfg.binary, Some(Binary::B64));
+        assert_eq!(cfg.f_compiler, Some(FortranCompiler::Flang));
+        assert_eq!(cfg.tuning.buffersize, Some(25));
+        assert_eq!(cfg.mode.sanitizers, vec![Sanitizer::Address]);
+        assert!(matches!(
+            Configure::from_toml("binary = 16"),
+            Err(Error::InvalidToml(_))
+        ));
+    }
+
+    #[test]
+    fn configure_from_partial_toml() {
+        let cfg = Configure::from_toml(
+            r#"
+# Only differences from default
+no_shared = true
+target = "SKYLAKEX"
+"#,
+        )
+        .unwrap();
+        assert!(cfg.no_shared);
+        assert_eq!(cfg.target, Some(Target::SKYLAKEX));
+
+        assert!(matches!(
+            Configure::from_toml("no_sharde = true"),
+            Err(Error::InvalidToml(_))
+        ));
+        assert!(matches!(
+            Configure::from_toml("no_shared = 1"),
+            Err(Error::InvalidToml(_))
+        ));
+        assert!(matches!(
+            Configure::from_toml("[tuning]\nbufersize = 25"),
+            Err(Error::InvalidToml(_))
+        ));
+        assert!(matches!(
+            Configure::from_toml("no_shared = true\nno_static = true"),
+            Err(Error::InvalidConfigure(ConfigureError::NoLibrary))
+        ));
+    }
+}
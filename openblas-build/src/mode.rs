@@ -0,0 +1,196 @@
+//! Debug and instrumented builds of OpenBLAS
+
+use crate::error::*;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// Sanitizer of GCC and Clang (`-fsanitize`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sanitizer {
+    /// AddressSanitizer
+    Address,
+    /// ThreadSanitizer
+    Thread,
+    /// UndefinedBehaviorSanitizer
+    Undefined,
+}
+
+impl Sanitizer {
+    /// Lowercase name used in `-fsanitize`, e.g. `address`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Undefined => "undefined",
+        }
+    }
+
+    /// Parse lowercase name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "address" => Some(Sanitizer::Address),
+            "thread" => Some(Sanitizer::Thread),
+            "undefined" => Some(Sanitizer::Undefined),
+            _ => None,
+        }
+    }
+
+    fn flag(&self) -> &'static str {
+        match self {
+            Sanitizer::Address => "-fsanitize=address",
+            Sanitizer::Thread => "-fsanitize=thread",
+            Sanitizer::Undefined => "-fsanitize=undefined",
+        }
+    }
+}
+
+/// Debug and instrumentation options of OpenBLAS
+///
+/// The default is an optimized build without instrumentation.
+///
+/// ```
+/// use openblas_build::*;
+/// let mut mode = BuildMode::default();
+/// mode.debug = true;
+/// mode.sanitizers = vec![Sanitizer::Address];
+/// let cfg = Configure::builder().mode(mode).build().unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct BuildMode {
+    /// Build without optimization and with debug information (`DEBUG=1`)
+    pub debug: bool,
+    /// Instrument C and Fortran code by sanitizers. Assembly kernels are not instrumented.
+    pub sanitizers: Vec<Sanitizer>,
+    /// Keep frame pointers for profilers (`-fno-omit-frame-pointer`)
+    pub frame_pointers: bool,
+}
+
+impl BuildMode {
+    /// Derive the mode from cargo's profile and rustc flags
+    ///
+    /// - `profile` is `PROFILE` of build.rs, and `debug = true` for `"debug"`
+    /// - `debuginfo` is `DEBUG` of build.rs, and `frame_pointers = true` if debug information is enabled
+    /// - `rustflags` is `CARGO_ENCODED_RUSTFLAGS`, where `-Z sanitizer` sets `sanitizers`
+    ///   and `-C force-frame-pointers` sets `frame_pointers`.
+    ///   Sanitizers not supported by OpenBLAS, e.g. `memory`, are ignored.
+    ///
+    /// ```
+    /// use openblas_build::*;
+    /// let mode = BuildMode::from_cargo("release", "false", "-Zsanitizer=address");
+    /// assert!(!mode.debug);
+    /// assert_eq!(mode.sanitizers, vec![Sanitizer::Address]);
+    /// assert!(BuildMode::from_cargo("debug", "true", "").debug);
+    /// ```
+    pub fn from_cargo(profile: &str, debuginfo: &str, rustflags: &str) -> Self {
+        let mut mode = BuildMode {
+            debug: profile == "debug",
+            sanitizers: Vec::new(),
+            frame_pointers: !matches!(debuginfo, "" | "0" | "false" | "none"),
+        };
+        let mut flags = rustflags.split('\x1f');
+        while let Some(flag) = flags.next() {
+            let (option, value) = match flag {
+                "-Z" => ("-Z", flags.next().unwrap_or("")),
+                "-C" | "--codegen" => ("-C", flags.next().unwrap_or("")),
+                _ if flag.starts_with("--codegen=") => ("-C", &flag["--codegen=".len()..]),
+                _ if flag.starts_with("-Z") || flag.starts_with("-C") => flag.split_at(2),
+                _ => continue,
+            };
+            let mut kv = value.trim().splitn(2, '=');
+            match (option, kv.next().unwrap_or(""), kv.next().unwrap_or("")) {
+                ("-Z", "sanitizer", names) => {
+                    for sanitizer in names.split(',').filter_map(Sanitizer::from_name) {
+                        if !mode.sanitizers.contains(&sanitizer) {
+                            mode.sanitizers.push(sanitizer);
+                        }
+                    }
+                }
+                ("-C", "force-frame-pointers", value) => {
+                    mode.frame_pointers = matches!(value, "" | "y" | "yes" | "on" | "true");
+                }
+                _ => {}
+            }
+        }
+        mode
+    }
+
+    /// Derive the mode from `PROFILE`, `DEBUG` and `CARGO_ENCODED_RUSTFLAGS`,
+    /// which cargo sets for build.rs. See [BuildMode::from_cargo] for detail.
+    pub fn from_cargo_env() -> Self {
+        let var = |name| env::var(name).unwrap_or_default();
+        Self::from_cargo(
+            &var("PROFILE"),
+            &var("DEBUG"),
+            &var("CARGO_ENCODED_RUSTFLAGS"),
+        )
+    }
+
+    /// Check the combination of sanitizers
+    pub fn validate(&self) -> Result<(), ConfigureError> {
+        if self.sanitizers.contains(&Sanitizer::Address)
+            && self.sanitizers.contains(&Sanitizer::Thread)
+        {
+            return Err(ConfigureError::IncompatibleSanitizers);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn make_args(&self) -> Vec<String> {
+        if self.debug {
+            vec!["DEBUG=1".into()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Flags for both C and Fortran compilers
+    pub(crate) fn flags(&self) -> Vec<&'static str> {
+        let mut flags: Vec<_> = self.sanitizers.iter().map(Sanitizer::flag).collect();
+        if self.frame_pointers {
+            flags.push("-fno-omit-frame-pointer");
+        }
+        flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_cargo() {
+        assert_eq!(
+            BuildMode::from_cargo("release", "false", ""),
+            BuildMode::default()
+        );
+
+        let mode = BuildMode::from_cargo(
+            "debug",
+            "0",
+            "-Z\x1fsanitizer=thread\x1f-Zsanitizer=memory,thread\x1f-Cforce-frame-pointers=yes",
+        );
+        assert!(mode.debug);
+        assert_eq!(mode.sanitizers, vec![Sanitizer::Thread]);
+        assert!(mode.frame_pointers);
+        assert!(mode.validate().is_ok());
+        assert_eq!(mode.make_args(), vec!["DEBUG=1"]);
+        assert_eq!(
+            mode.flags(),
+            vec!["-fsanitize=thread", "-fno-omit-frame-pointer"]
+        );
+
+        // Debug information in release profile, e.g. for profiling
+        let mode = BuildMode::from_cargo("release", "line-tables-only", "");
+        assert!(!mode.debug);
+        assert!(mode.frame_pointers);
+    }
+
+    #[test]
+    fn incompatible_sanitizers() {
+        let mode = BuildMode::from_cargo("release", "false", "-Zsanitizer=address,thread");
+        assert_eq!(mode.validate(), Err(ConfigureError::IncompatibleSanitizers));
+    }
+}
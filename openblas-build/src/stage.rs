@@ -0,0 +1,239 @@
+//! Stage the OpenBLAS source into the build directory
+
+use crate::error::*;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::{fs, io, os::unix::fs::symlink, path::*};
+use walkdir::WalkDir;
+
+/// Directories not needed to build the libraries, which are also excluded from the package of openblas-src
+const EXCLUDED: &[&str] = &[
+    ".git",
+    "benchmark",
+    "lapack-netlib/BLAS/TESTING",
+    "lapack-netlib/CBLAS/testing",
+    "lapack-netlib/TESTING/EIG",
+    "lapack-netlib/TESTING/LIN",
+    "reference",
+];
+
+/// Tests of OpenBLAS, staged only for [crate::MakeGoal::Tests]
+const TESTS: &[&str] = &["ctest", "test", "utest"];
+
+fn is_excluded(rel: &Path, with_tests: bool) -> bool {
+    if EXCLUDED.iter().any(|dir| rel == Path::new(dir)) {
+        return true;
+    }
+    if !with_tests && TESTS.iter().any(|dir| rel == Path::new(dir)) {
+        return true;
+    }
+    // Inputs of LAPACK tests, e.g. `lapack-netlib/TESTING/dbal.in`
+    rel.parent() == Some(Path::new("lapack-netlib/TESTING"))
+        && rel.extension() == Some("in".as_ref())
+}
+
+/// How to stage files, downgraded when the filesystem does not support it
+///
+/// Files are not hard-linked, since an in-place edit of the source would change the staged file too,
+/// and then it cannot be detected as outdated. Writes in the build directory would also reach the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    /// Copy-on-write clone (`FICLONE`), e.g. on Btrfs and XFS. Only on Linux.
+    Reflink,
+    Copy,
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dest: &Path) -> io::Result<()> {
+    let src = fs::File::open(src)?;
+    let dest = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)?;
+    if unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Stage a regular file by `method`, and keep its permissions and modification time
+fn stage_file(src: &Path, dest: &Path, method: &mut Method) -> io::Result<()> {
+    if *method == Method::Reflink {
+        if reflink(src, dest).is_ok() {
+            let meta = fs::metadata(src)?;
+            fs::set_permissions(dest, meta.permissions())?;
+            return fs::File::options()
+                .write(true)
+                .open(dest)?
+                .set_modified(meta.modified()?);
+        }
+        fs::remove_file(dest).ok();
+        *method = Method::Copy;
+    }
+    // `fs::copy` keeps the permissions
+    fs::copy(src, dest)?;
+    fs::File::options()
+        .write(true)
+        .open(dest)?
+        .set_modified(fs::metadata(src)?.modified()?)
+}
+
+/// Check the staged file `dest` is same as `src` by the size and modification time
+fn is_up_to_date(src: &fs::Metadata, dest: &Path) -> io::Result<bool> {
+    let dest = match fs::symlink_metadata(dest) {
+        Ok(dest) => dest,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    Ok(dest.file_type() == src.file_type()
+        && dest.len() == src.len()
+        && dest.modified()? == src.modified()?)
+}
+
+/// Stage the source tree of OpenBLAS at `root` into `out_dir`
+///
+/// Files are reflinked if the filesystem supports it, and copied otherwise.
+/// Files already staged are kept if up to date, and outdated ones are replaced.
+/// Files not in `root`, e.g. objects of a previous build, are kept as is.
+///
+/// Returns the number of replaced files.
+pub(crate) fn stage_source(root: &Path, out_dir: &Path, with_tests: bool) -> Result<usize, Error> {
+    let mut method = Method::Reflink;
+    let mut replaced = 0;
+    let walk = WalkDir::new(root).into_iter().filter_entry(|entry| {
+        let rel = entry
+            .path()
+            .strip_prefix(root)
+            .expect("Directory entry is not under root");
+        !is_excluded(rel, with_tests)
+    });
+    for entry in walk {
+        let entry = entry.map_err(io::Error::from)?;
+        let dest = out_dir.join(
+            entry
+                .path()
+                .strip_prefix(root)
+                .expect("Directory entry is not under root"),
+        );
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            fs::create_dir_all(&dest)?;
+            continue;
+        }
+        let meta = entry.metadata().map_err(io::Error::from)?;
+        if is_up_to_date(&meta, &dest)? {
+            continue;
+        }
+        if fs::symlink_metadata(&dest).is_ok() {
+            fs::remove_file(&dest)?;
+            replaced += 1;
+        }
+        if file_type.is_symlink() {
+            symlink(fs::read_link(entry.path())?, &dest)?;
+        } else {
+            stage_file(entry.path(), &dest, &mut method)?;
+        }
+    }
+    Ok(replaced)
+}
+
+/// Remove everything in `out_dir`, e.g. objects and libraries of a stale build, and stage the source again
+///
+/// This replaces `make clean`, which enters directories not staged, e.g. `reference`.
+pub(crate) fn restage_source(root: &Path, out_dir: &Path, with_tests: bool) -> Result<(), Error> {
+    fs::remove_dir_all(out_dir)?;
+    fs::create_dir_all(out_dir)?;
+    stage_source(root, out_dir, with_tests)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn excluded() {
+        assert!(is_excluded(Path::new("benchmark"), false));
+        assert!(is_excluded(Path::new("lapack-netlib/TESTING/EIG"), true));
+        assert!(is_excluded(
+            Path::new("lapack-netlib/TESTING/dbal.in"),
+            true
+        ));
+        assert!(!is_excluded(
+            Path::new("lapack-netlib/TESTING/Makefile"),
+            true
+        ));
+        assert!(is_excluded(Path::new("ctest"), false));
+        assert!(!is_excluded(Path::new("ctest"), true));
+        assert!(!is_excluded(Path::new("kernel/x86_64/test"), false));
+    }
+
+    #[test]
+    fn stage() {
+        let tmp = std::env::temp_dir().join(format!("openblas-build-stage-{}", std::process::id()));
+        let (root, out_dir) = (tmp.join("source"), tmp.join("out"));
+        fs::create_dir_all(root.join("driver")).unwrap();
+        fs::create_dir_all(root.join("benchmark")).unwrap();
+        fs::write(root.join("driver/level3.c"), "level3").unwrap();
+        fs::write(root.join("benchmark/gemm.c"), "gemm").unwrap();
+
+        assert_eq!(stage_source(&root, &out_dir, false).unwrap(), 0);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("driver/level3.c")).unwrap(),
+            "level3"
+        );
+        assert!(!out_dir.join("benchmark").exists());
+
+        // Objects are kept, and unchanged sources are not replaced
+        fs::write(out_dir.join("driver/level3.o"), "object").unwrap();
+        assert_eq!(stage_source(&root, &out_dir, false).unwrap(), 0);
+        assert!(out_dir.join("driver/level3.o").exists());
+
+        // Replaced as a new file, e.g. by `git checkout`
+        fs::remove_file(root.join("driver/level3.c")).unwrap();
+        fs::write(root.join("driver/level3.c"), "level3 updated").unwrap();
+        assert_eq!(stage_source(&root, &out_dir, false).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("driver/level3.c")).unwrap(),
+            "level3 updated"
+        );
+        assert_eq!(
+            fs::metadata(out_dir.join("driver/level3.c"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            fs::metadata(root.join("driver/level3.c"))
+                .unwrap()
+                .modified()
+                .unwrap()
+        );
+
+        // Edited in place, e.g. by an editor or `sed -i` keeping the inode
+        fs::OpenOptions::new()
+            .append(true)
+            .open(root.join("driver/level3.c"))
+            .unwrap()
+            .write_all(b" edited")
+            .unwrap();
+        assert_eq!(stage_source(&root, &out_dir, false).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("driver/level3.c")).unwrap(),
+            "level3 updated edited"
+        );
+
+        // Objects of a stale build are removed
+        restage_source(&root, &out_dir, false).unwrap();
+        assert!(!out_dir.join("driver/level3.o").exists());
+        assert_eq!(
+            fs::read_to_string(out_dir.join("driver/level3.c")).unwrap(),
+            "level3 updated edited"
+        );
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+}
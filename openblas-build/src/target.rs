@@ -0,0 +1,461 @@
+//! CPU targets of OpenBLAS
+
+use crate::error::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, fs, path::*, str::FromStr, sync::Mutex};
+
+macro_rules! targets {
+    ($($name:ident,)*) => {
+        /// CPU list in [TargetList](https://github.com/xianyi/OpenBLAS/blob/v0.3.26/TargetList.txt)
+        ///
+        /// Targets supported by a newer OpenBLAS but not listed here
+        /// can be used as [Target::Custom] through [TargetList].
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[allow(non_camel_case_types)] // to use original identifiers
+        pub enum Target {
+            $($name,)*
+            /// Target listed in `TargetList.txt` of the OpenBLAS source, but unknown to this crate.
+            /// Names read at runtime are interned by [Target::custom].
+            Custom(&'static str),
+        }
+
+        impl Target {
+            /// All targets known to this crate, i.e. except [Target::Custom]
+            pub const KNOWN: &'static [Target] = &[$(Target::$name,)*];
+
+            /// Identifier used in OpenBLAS, e.g. `"HASWELL"`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Target::$name => stringify!($name),)*
+                    Target::Custom(name) => name,
+                }
+            }
+        }
+    };
+}
+
+targets! {
+    // X86/X86_64 Intel
+    P2,
+    KATMAI,
+    COPPERMINE,
+    NORTHWOOD,
+    PRESCOTT,
+    BANIAS,
+    YONAH,
+    CORE2,
+    PENRYN,
+    DUNNINGTON,
+    NEHALEM,
+    SANDYBRIDGE,
+    HASWELL,
+    SKYLAKEX,
+    ATOM,
+    COOPERLAKE,
+    SAPPHIRERAPIDS,
+
+    // X86/X86_64 AMD
+    ATHLON,
+    OPTERON,
+    OPTERON_SSE3,
+    BARCELONA,
+    SHANGHAI,
+    ISTANBUL,
+    BOBCAT,
+    BULLDOZER,
+    PILEDRIVER,
+    STEAMROLLER,
+    EXCAVATOR,
+    ZEN,
+
+    // X86/X86_64 generic
+    SSE_GENERIC,
+    VIAC3,
+    NANO,
+
+    // Power
+    POWER4,
+    POWER5,
+    POWER6,
+    POWER7,
+    POWER8,
+    POWER9,
+    POWER10,
+    PPCG4,
+    PPC970,
+    PPC970MP,
+    PPC440,
+    PPC440FP2,
+    CELL,
+
+    // MIPS
+    P5600,
+    MIPS1004K,
+    MIPS24K,
+
+    // MIPS64
+    MIPS64_GENERIC,
+    SICORTEX,
+    LOONGSON3A,
+    LOONGSON3B,
+    I6400,
+    P6600,
+    I6500,
+
+    // IA64
+    ITANIUM2,
+
+    // Sparc
+    SPARC,
+    SPARCV7,
+
+    // ARM
+    CORTEXA15,
+    CORTEXA9,
+    ARMV7,
+    ARMV6,
+    ARMV5,
+
+    // ARM64
+    ARMV8,
+    CORTEXA53,
+    CORTEXA55,
+    CORTEXA57,
+    CORTEXA72,
+    CORTEXA73,
+    CORTEXA76,
+    CORTEXA510,
+    CORTEXA710,
+    CORTEXX1,
+    CORTEXX2,
+    NEOVERSEN1,
+    NEOVERSEV1,
+    NEOVERSEN2,
+    EMAG8180,
+    FALKOR,
+    THUNDERX,
+    THUNDERX2T99,
+    TSV110,
+    THUNDERX3T110,
+    VORTEX,
+    A64FX,
+    ARMV8SVE,
+    FT2000,
+
+    // System Z
+    ZARCH_GENERIC,
+    Z13,
+    Z14,
+    Z15,
+
+    // RISC-V 64
+    RISCV64_GENERIC,
+    RISCV64_ZVL128B,
+    RISCV64_ZVL256B,
+    C910V,
+    x280,
+
+    // LoongArch64
+    LOONGSONGENERIC,
+    LOONGSON3R5,
+    LOONGSON2K1000,
+
+    // Elbrus E2000
+    E2K,
+
+    // Alpha
+    EV4,
+    EV5,
+    EV6,
+}
+
+/// Architecture family of [Target], corresponding to sections of TargetList
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchFamily {
+    /// X86 and X86_64
+    X86_64,
+    /// Power and PowerPC
+    Power,
+    Mips,
+    Mips64,
+    IA64,
+    Sparc,
+    Arm,
+    Arm64,
+    /// System Z
+    ZArch,
+    RiscV64,
+    LoongArch64,
+    E2K,
+    Alpha,
+}
+
+impl ArchFamily {
+    /// Values of Rust's `target_arch` which targets of this family can be built for
+    ///
+    /// Empty if Rust does not support this architecture.
+    pub fn target_arches(&self) -> &'static [&'static str] {
+        match self {
+            ArchFamily::X86_64 => &["x86", "x86_64"],
+            ArchFamily::Power => &["powerpc", "powerpc64"],
+            ArchFamily::Mips => &["mips"],
+            ArchFamily::Mips64 => &["mips64"],
+            ArchFamily::IA64 => &[],
+            ArchFamily::Sparc => &["sparc", "sparc64"],
+            ArchFamily::Arm => &["arm"],
+            ArchFamily::Arm64 => &["aarch64"],
+            ArchFamily::ZArch => &["s390x"],
+            ArchFamily::RiscV64 => &["riscv64"],
+            ArchFamily::LoongArch64 => &["loongarch64"],
+            ArchFamily::E2K => &[],
+            ArchFamily::Alpha => &[],
+        }
+    }
+}
+
+impl Target {
+    /// Architecture family, `None` for [Target::Custom]
+    pub fn family(&self) -> Option<ArchFamily> {
+        use Target::*;
+        Some(match self {
+            P2 | KATMAI | COPPERMINE | NORTHWOOD | PRESCOTT | BANIAS | YONAH | CORE2 | PENRYN
+            | DUNNINGTON | NEHALEM | SANDYBRIDGE | HASWELL | SKYLAKEX | ATOM | COOPERLAKE
+            | SAPPHIRERAPIDS | ATHLON | OPTERON | OPTERON_SSE3 | BARCELONA | SHANGHAI
+            | ISTANBUL | BOBCAT | BULLDOZER | PILEDRIVER | STEAMROLLER | EXCAVATOR | ZEN
+            | SSE_GENERIC | VIAC3 | NANO => ArchFamily::X86_64,
+            POWER4 | POWER5 | POWER6 | POWER7 | POWER8 | POWER9 | POWER10 | PPCG4 | PPC970
+            | PPC970MP | PPC440 | PPC440FP2 | CELL => ArchFamily::Power,
+            P5600 | MIPS1004K | MIPS24K => ArchFamily::Mips,
+            MIPS64_GENERIC | SICORTEX | LOONGSON3A | LOONGSON3B | I6400 | P6600 | I6500 => {
+                ArchFamily::Mips64
+            }
+            ITANIUM2 => ArchFamily::IA64,
+            SPARC | SPARCV7 => ArchFamily::Sparc,
+            CORTEXA15 | CORTEXA9 | ARMV7 | ARMV6 | ARMV5 => ArchFamily::Arm,
+            ARMV8 | CORTEXA53 | CORTEXA55 | CORTEXA57 | CORTEXA72 | CORTEXA73 | CORTEXA76
+            | CORTEXA510 | CORTEXA710 | CORTEXX1 | CORTEXX2 | NEOVERSEN1 | NEOVERSEV1
+            | NEOVERSEN2 | EMAG8180 | FALKOR | THUNDERX | THUNDERX2T99 | TSV110 | THUNDERX3T110
+            | VORTEX | A64FX | ARMV8SVE | FT2000 => ArchFamily::Arm64,
+            ZARCH_GENERIC | Z13 | Z14 | Z15 => ArchFamily::ZArch,
+            RISCV64_GENERIC | RISCV64_ZVL128B | RISCV64_ZVL256B | C910V | x280 => {
+                ArchFamily::RiscV64
+            }
+            LOONGSONGENERIC | LOONGSON3R5 | LOONGSON2K1000 => ArchFamily::LoongArch64,
+            E2K => ArchFamily::E2K,
+            EV4 | EV5 | EV6 => ArchFamily::Alpha,
+            Custom(_) => return None,
+        })
+    }
+
+    /// Check this target can be built for Rust's `target_arch`, e.g. `"x86_64"`
+    ///
+    /// Always true for [Target::Custom] since its family is unknown.
+    pub fn is_valid_for(&self, target_arch: &str) -> bool {
+        match self.family() {
+            Some(family) => family.target_arches().contains(&target_arch),
+            None => true,
+        }
+    }
+}
+
+/// Names of [Target::Custom] read at runtime, leaked once per name
+static CUSTOM_NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+impl Target {
+    /// [Target::Custom] of `name` not known at compile time, e.g. read from `TargetList.txt`
+    ///
+    /// The name is interned to keep [Target] `Copy`, and is not checked to be a valid target.
+    pub fn custom(name: &str) -> Target {
+        let mut names = CUSTOM_NAMES.lock().unwrap();
+        if let Some(interned) = names.iter().find(|interned| **interned == name) {
+            return Target::Custom(interned);
+        }
+        let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+        names.push(interned);
+        Target::Custom(interned)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parse OpenBLAS identifier, e.g. `"HASWELL"` or `"haswell"`
+///
+/// Only targets known to this crate are accepted. Use [TargetList] for others.
+///
+/// ```
+/// use openblas_build::*;
+/// let target: Target = "skylakex".parse().unwrap();
+/// assert_eq!(target, Target::SKYLAKEX);
+/// assert_eq!(target.to_string(), "SKYLAKEX");
+/// ```
+impl FromStr for Target {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let s = s.trim();
+        Target::KNOWN
+            .iter()
+            .find(|target| target.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| Error::UnknownTarget { name: s.into() })
+    }
+}
+
+/// Name of the target in TOML
+impl Serialize for Target {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// Targets unknown to this crate are [Target::Custom], as in [TargetList]
+impl<'de> Deserialize<'de> for Target {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(name.parse().unwrap_or_else(|_| Target::custom(&name)))
+    }
+}
+
+/// Targets listed in `TargetList.txt` of an OpenBLAS source tree
+///
+/// This allows to use targets supported by the vendored OpenBLAS
+/// before this crate knows them as variants of [Target].
+///
+/// ```no_run
+/// use openblas_build::*;
+/// let list = TargetList::new("openblas-src/source").unwrap();
+/// let target = list.parse("NEWCPU").unwrap(); // Target::Custom("NEWCPU")
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetList {
+    /// Identifiers in the order of `TargetList.txt`
+    pub names: Vec<String>,
+}
+
+impl TargetList {
+    /// Read `TargetList.txt` in the root of OpenBLAS source
+    pub fn new(openblas_root: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = openblas_root.as_ref().join("TargetList.txt");
+        let content = fs::read_to_string(&path).map_err(|_| Error::TargetListNotExist { path })?;
+        Ok(Self::parse_content(&content))
+    }
+
+    /// Parse the content of `TargetList.txt`
+    ///
+    /// The file is written for human, and this assumes the following format:
+    ///
+    /// ```text
+    /// 1.X86/X86_64
+    /// a)Intel CPU:
+    /// P2
+    /// KATMAI
+    /// ...
+    /// 10.RISC-V 64:
+    /// RISCV64_GENERIC (e.g. PolarFire Soc/SiFive U54)
+    /// ```
+    ///
+    /// i.e. the first word of a line is an identifier if it consists of alphanumerics and `_`,
+    /// and lines containing `:` or `=` are headers or examples.
+    pub fn parse_content(content: &str) -> Self {
+        let mut names: Vec<String> = Vec::new();
+        for line in content.lines() {
+            if line.contains(':') || line.contains('=') {
+                continue;
+            }
+            let word = match line.split_whitespace().next() {
+                Some(word) => word,
+                None => continue,
+            };
+            if word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !names.iter().any(|name| name == word)
+            {
+                names.push(word.into());
+            }
+        }
+        TargetList { names }
+    }
+
+    /// Check `name` is listed
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n.eq_ignore_ascii_case(name))
+    }
+
+    /// Parse target name listed in `TargetList.txt`
+    ///
+    /// Returns a variant of [Target] if it is known to this crate,
+    /// and [Target::Custom] if it is only listed in `TargetList.txt`.
+    pub fn parse(&self, name: &str) -> Result<Target, Error> {
+        let name = name.trim();
+        if let Ok(target) = name.parse() {
+            return Ok(target);
+        }
+        self.names
+            .iter()
+            .find(|n| n.eq_ignore_ascii_case(name))
+            .map(|n| Target::custom(n))
+            .ok_or_else(|| Error::UnknownTarget { name: name.into() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_round_trip() {
+        for target in Target::KNOWN {
+            assert_eq!(&target.to_string().parse::<Target>().unwrap(), target);
+        }
+        assert!("NOSUCHCPU".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn target_arch() {
+        assert_eq!(Target::POWER9.family(), Some(ArchFamily::Power));
+        assert!(!Target::POWER9.is_valid_for("x86_64"));
+        assert!(Target::HASWELL.is_valid_for("x86_64"));
+        assert!(Target::NEOVERSEV1.is_valid_for("aarch64"));
+        assert!(Target::Custom("NEWCPU").is_valid_for("x86_64"));
+        for target in Target::KNOWN {
+            assert!(target.family().is_some());
+        }
+    }
+
+    #[test]
+    fn parse_target_list() {
+        let list = TargetList::parse_content(
+            r#"
+Force Target Examples:
+
+make TARGET=NEHALEM
+
+1.X86/X86_64
+a)Intel CPU:
+P2
+SAPPHIRERAPIDS
+
+10.RISC-V 64:
+RISCV64_GENERIC (e.g. PolarFire Soc/SiFive U54)
+x280
+
+99.Future CPU:
+NEWCPU
+"#,
+        );
+        assert_eq!(
+            list.names,
+            vec!["P2", "SAPPHIRERAPIDS", "RISCV64_GENERIC", "x280", "NEWCPU"]
+        );
+        assert_eq!(list.parse("x280").unwrap(), Target::x280);
+        let newcpu = list.parse("newcpu").unwrap();
+        assert_eq!(newcpu, Target::Custom("NEWCPU"));
+        assert_eq!(
+            newcpu.name().as_ptr(),
+            Target::custom("NEWCPU").name().as_ptr()
+        );
+        assert!(list.parse("NOSUCHCPU").is_err());
+    }
+}
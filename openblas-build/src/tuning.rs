@@ -0,0 +1,122 @@
+//! Build-time performance tuning of OpenBLAS
+
+use crate::error::*;
+use serde::{Deserialize, Serialize};
+
+/// Range of `buffersize`, i.e. the memory buffer from 32 MiB (`32 << 20`) to 32 GiB (`32 << 30`)
+const BUFFERSIZE_RANGE: (usize, usize) = (20, 30);
+
+/// Performance tuning options of OpenBLAS
+///
+/// All options are the default of OpenBLAS if None or false.
+///
+/// ```
+/// use openblas_build::*;
+/// let mut tuning = Tuning::default();
+/// tuning.small_matrix_opt = Some(true);
+/// tuning.buffersize = Some(25);
+/// let cfg = Configure::builder().tuning(tuning).build().unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Tuning {
+    /// Enable (`Some(true)`) or disable (`Some(false)`) the optimized kernels for small matrices (`SMALL_MATRIX_OPT`)
+    pub small_matrix_opt: Option<bool>,
+    /// GEMM is multithreaded if `M * N * K` exceeds `65536 * threshold` (`GEMM_MULTITHREAD_THRESHOLD`).
+    /// Requires `use_thread`.
+    pub gemm_multithread_threshold: Option<usize>,
+    /// The memory buffer is `32 << buffersize` bytes (`BUFFERSIZE`), from 20 to 30
+    pub buffersize: Option<usize>,
+    /// Allocate the memory buffer from huge pages (`HUGETLB_ALLOCATION`)
+    pub hugetlb_allocation: bool,
+    /// Propagate the floating point control register of the main thread to the worker threads (`CONSISTENT_FPCSR`)
+    pub consistent_fpcsr: bool,
+}
+
+impl Tuning {
+    /// Check the ranges of options
+    pub fn validate(&self, use_thread: bool) -> Result<(), ConfigureError> {
+        if let Some(threshold) = self.gemm_multithread_threshold {
+            if !use_thread {
+                return Err(ConfigureError::RequiresThread {
+                    option: "gemm_multithread_threshold",
+                });
+            }
+            if threshold == 0 {
+                return Err(ConfigureError::OutOfRange {
+                    option: "gemm_multithread_threshold",
+                    value: threshold,
+                    min: 1,
+                    max: usize::MAX,
+                });
+            }
+        }
+        if let Some(buffersize) = self.buffersize {
+            let (min, max) = BUFFERSIZE_RANGE;
+            if buffersize < min || buffersize > max {
+                return Err(ConfigureError::OutOfRange {
+                    option: "buffersize",
+                    value: buffersize,
+                    min,
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn make_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(small_matrix_opt) = self.small_matrix_opt {
+            args.push(format!("SMALL_MATRIX_OPT={}", small_matrix_opt as i32))
+        }
+        if let Some(threshold) = self.gemm_multithread_threshold {
+            args.push(format!("GEMM_MULTITHREAD_THRESHOLD={}", threshold))
+        }
+        if let Some(buffersize) = self.buffersize {
+            args.push(format!("BUFFERSIZE={}", buffersize))
+        }
+        if self.hugetlb_allocation {
+            args.push("HUGETLB_ALLOCATION=1".into())
+        }
+        if self.consistent_fpcsr {
+            args.push("CONSISTENT_FPCSR=1".into())
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let mut tuning = Tuning::default();
+        assert!(tuning.validate(false).is_ok());
+
+        tuning.buffersize = Some(31);
+        assert!(matches!(
+            tuning.validate(false),
+            Err(ConfigureError::OutOfRange {
+                option: "buffersize",
+                ..
+            })
+        ));
+
+        tuning.buffersize = Some(25);
+        tuning.gemm_multithread_threshold = Some(8);
+        assert_eq!(
+            tuning.validate(false),
+            Err(ConfigureError::RequiresThread {
+                option: "gemm_multithread_threshold"
+            })
+        );
+        assert!(tuning.validate(true).is_ok());
+        assert_eq!(
+            tuning.make_args(),
+            vec!["GEMM_MULTITHREAD_THRESHOLD=8", "BUFFERSIZE=25"]
+        );
+    }
+}
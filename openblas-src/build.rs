@@ -1,7 +1,52 @@
-use std::{env, path::*, process::Command};
+use std::{env, fmt, fs, path::*, process::Command};
 
 fn feature_enabled(feature: &str) -> bool {
-    env::var(format!("CARGO_FEATURE_{}", feature.to_uppercase())).is_ok()
+    env::var(format!(
+        "CARGO_FEATURE_{}",
+        feature.to_uppercase().replace('-', "_")
+    ))
+    .is_ok()
+}
+
+/// Publish `cargo:KEY=VALUE` to build scripts of dependent crates as `DEP_OPENBLAS_KEY`,
+/// since this crate declares `links = "openblas"`
+fn metadata(key: &str, value: impl fmt::Display) {
+    println!("cargo:{}={}", key, value);
+}
+
+/// Directories of OpenBLAS installed in the system, or by `make install`
+#[derive(Debug, Default)]
+struct Installed {
+    include: Option<PathBuf>,
+    lib_dir: Option<PathBuf>,
+}
+
+impl Installed {
+    /// Publish the directories, and what is found in the headers
+    ///
+    /// - `interface` is `ilp64` if `openblas_config.h` defines `OPENBLAS_USE64BITINT`, otherwise `lp64`
+    /// - `cblas` and `lapacke` are `1` if `cblas.h` and `lapacke.h` exist, otherwise `0`
+    ///
+    /// The threading model and the symbol affixes are unknown from the headers, and not published.
+    fn publish(&self) {
+        if let Some(lib_dir) = &self.lib_dir {
+            metadata("lib_dir", lib_dir.display());
+        }
+        let include = match &self.include {
+            Some(include) => include,
+            None => return,
+        };
+        metadata("include", include.display());
+        if let Ok(config) = fs::read_to_string(include.join("openblas_config.h")) {
+            let ilp64 = config.lines().any(|line| {
+                let mut words = line.split_whitespace();
+                words.next() == Some("#define") && words.next() == Some("OPENBLAS_USE64BITINT")
+            });
+            metadata("interface", if ilp64 { "ilp64" } else { "lp64" });
+        }
+        metadata("cblas", include.join("cblas.h").exists() as i32);
+        metadata("lapacke", include.join("lapacke.h").exists() as i32);
+    }
 }
 
 /// Add path where pacman (on msys2) install OpenBLAS
@@ -12,37 +57,57 @@ fn feature_enabled(feature: &str) -> bool {
 /// - But we have to specify them using `-L` in **Windows manner**
 ///   - msys2 `/` is `C:\msys64\` in Windows by default install
 ///   - It can be convert using `cygpath` command
-fn windows_gnu_system() {
-    let lib_path = String::from_utf8(
-        Command::new("cygpath")
-            .arg("-w")
-            .arg(if feature_enabled("static") {
-                "/mingw64/bin"
-            } else {
-                "/mingw64/lib"
-            })
-            .output()
-            .expect("Failed to exec cygpath")
-            .stdout,
-    )
-    .expect("cygpath output includes non UTF-8 string");
+/// - Headers are installed into `/mingw64/include/OpenBLAS`
+fn windows_gnu_system() -> Installed {
+    let cygpath = |path: &str| {
+        String::from_utf8(
+            Command::new("cygpath")
+                .arg("-w")
+                .arg(path)
+                .output()
+                .expect("Failed to exec cygpath")
+                .stdout,
+        )
+        .expect("cygpath output includes non UTF-8 string")
+        .trim()
+        .to_string()
+    };
+    let lib_path = cygpath(if feature_enabled("static") {
+        "/mingw64/bin"
+    } else {
+        "/mingw64/lib"
+    });
     println!("cargo:rustc-link-search={}", lib_path);
+    Installed {
+        include: Some(cygpath("/mingw64/include/OpenBLAS").into()),
+        lib_dir: Some(lib_path.into()),
+    }
 }
 
 /// Use vcpkg for msvc "system" feature
-fn windows_msvc_system() {
+fn windows_msvc_system() -> Installed {
     if feature_enabled("static") {
         env::set_var("CARGO_CFG_TARGET_FEATURE", "crt-static");
     } else {
         env::set_var("VCPKGRS_DYNAMIC", "1");
     }
-    #[cfg(target_env = "msvc")]
-    vcpkg::find_package("openblas").unwrap();
-    if !cfg!(target_env = "msvc") {
-        unreachable!();
+    vcpkg_find_openblas()
+}
+
+#[cfg(target_env = "msvc")]
+fn vcpkg_find_openblas() -> Installed {
+    let lib = vcpkg::find_package("openblas").unwrap();
+    Installed {
+        include: lib.include_paths.first().cloned(),
+        lib_dir: lib.link_paths.first().cloned(),
     }
 }
 
+#[cfg(not(target_env = "msvc"))]
+fn vcpkg_find_openblas() -> Installed {
+    unreachable!();
+}
+
 /// homebrew says
 ///
 /// > openblas is keg-only, which means it was not symlinked into /usr/local,
@@ -53,81 +118,127 @@ fn windows_msvc_system() {
 /// export LDFLAGS="-L/usr/local/opt/openblas/lib"
 /// export CPPFLAGS="-I/usr/local/opt/openblas/include"
 /// ```
-fn macos_system() {
+fn macos_system() -> Installed {
     println!("cargo:rustc-link-search=/usr/local/opt/openblas/lib");
+    Installed {
+        include: Some("/usr/local/opt/openblas/include".into()),
+        lib_dir: Some("/usr/local/opt/openblas/lib".into()),
+    }
+}
+
+/// Use the directories of `openblas.pc` if registered to pkg-config, e.g. by a distribution package
+fn pkg_config_system() -> Installed {
+    let variable = |name: &str| {
+        let out = Command::new("pkg-config")
+            .args(["--variable", name, "openblas"])
+            .output()
+            .ok()
+            .filter(|out| out.status.success())?;
+        let value = String::from_utf8(out.stdout).ok()?;
+        Some(PathBuf::from(value.trim())).filter(|path| !path.as_os_str().is_empty())
+    };
+    let installed = Installed {
+        include: variable("includedir"),
+        lib_dir: variable("libdir"),
+    };
+    if let Some(lib_dir) = &installed.lib_dir {
+        println!("cargo:rustc-link-search={}", lib_dir.display());
+    }
+    installed
 }
 
 fn main() {
+    // Any `rerun-if-*` disables the default of cargo, which reruns this script if any file in this package changes
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=source");
     let link_kind = if feature_enabled("static") {
         "static"
     } else {
         "dylib"
     };
     if feature_enabled("system") {
-        if cfg!(target_os = "windows") {
+        let installed = if cfg!(target_os = "windows") {
             if cfg!(target_env = "gnu") {
-                windows_gnu_system();
+                windows_gnu_system()
             } else if cfg!(target_env = "msvc") {
-                windows_msvc_system();
+                windows_msvc_system()
             } else {
                 panic!(
                     "Unsupported ABI for Windows: {}",
                     env::var("CARGO_CFG_TARGET_ENV").unwrap()
                 );
             }
-        }
-        if cfg!(target_os = "macos") {
-            macos_system();
-        }
+        } else if cfg!(target_os = "macos") {
+            macos_system()
+        } else {
+            pkg_config_system()
+        };
         println!("cargo:rustc-link-lib={}=openblas", link_kind);
+        installed.publish();
     } else {
         if cfg!(target_env = "msvc") {
             panic!(
                 "Non-vcpkg builds are not supported on Windows. You must use the 'system' feature."
             )
         }
-        build();
+        build(link_kind);
     }
-    println!("cargo:rustc-link-lib={}=openblas", link_kind);
 }
 
 /// Build OpenBLAS using openblas-build crate
 #[cfg(target_os = "linux")]
-fn build() {
-    let mut cfg = openblas_build::Configure::default();
+fn build(link_kind: &str) {
+    // `OPENBLAS_*` environment variables are read in the same manner as other platforms,
+    // or all options are loaded from the TOML file specified by `OPENBLAS_CONFIGURE`.
+    // Features have priority over them.
+    for name in openblas_build::Configure::ENV_VARS {
+        println!("cargo:rerun-if-env-changed={}", name);
+    }
+    println!("cargo:rerun-if-env-changed=OPENBLAS_CONFIGURE");
+    let mut cfg = match env::var("OPENBLAS_CONFIGURE") {
+        Ok(path) => {
+            println!("cargo:rerun-if-changed={}", path);
+            openblas_build::Configure::load(path).unwrap()
+        }
+        Err(_) => openblas_build::Configure::from_env().unwrap(),
+    };
     if !feature_enabled("cblas") {
         cfg.no_cblas = true;
     }
     if !feature_enabled("lapacke") {
         cfg.no_lapacke = true;
     }
+    if cfg.external_lapack.is_some() && feature_enabled("lapacke") {
+        // LAPACK (and LAPACKE) is provided by the external library
+        println!("cargo:warning=LAPACKE is not built with OPENBLAS_EXTERNAL_LAPACK");
+    }
     if feature_enabled("static") {
         cfg.no_shared = true;
     } else {
         cfg.no_static = true;
     }
+    if cfg.target.is_none() && feature_enabled("target-from-rustc") {
+        // Tune OpenBLAS for the CPU which Rust code is compiled for by `-C target-cpu`,
+        // instead of the CPU running this build script.
+        cfg.target = openblas_build::Target::from_cargo_env();
+    }
+    if cfg.mode == openblas_build::BuildMode::default() && feature_enabled("mode-from-cargo") {
+        // e.g. `DEBUG=1` in the dev profile, and `-fsanitize=address` with `-Z sanitizer=address`
+        cfg.mode = openblas_build::BuildMode::from_cargo_env();
+    }
+    // `CC`, `AR` and `CFLAGS` are resolved in the same manner as cc-rs, e.g. `CC_aarch64_unknown_linux_gnu`
+    let (target, host) = (env::var("TARGET").unwrap(), env::var("HOST").unwrap());
+    for name in openblas_build::Configure::cc_env_vars(&target, &host) {
+        println!("cargo:rerun-if-env-changed={}", name);
+    }
+    cfg.fill_cc_env(&target, &host);
+    // Cross-compile with the GNU toolchain for the target by default, e.g. `aarch64-linux-gnu-gcc`
+    cfg.fill_cross_defaults(&target, &host);
 
-    let output = if feature_enabled("cache") {
-        use std::{collections::hash_map::DefaultHasher, hash::*};
-        // Build OpenBLAS on user's data directory.
-        // See https://docs.rs/dirs/3.0.1/dirs/fn.data_dir.html
-        //
-        // On Linux, `data_dir` returns `$XDG_DATA_HOME` or `$HOME/.local/share`.
-        // This build script creates a directory based on the hash value of `cfg`,
-        // i.e. `$XDG_DATA_HOME/openblas_build/[Hash of cfg]`, and build OpenBLAS there.
-        //
-        // This build will be shared among several projects using openblas-src crate.
-        // It makes users not to build OpenBLAS in every `cargo build`.
-        let mut hasher = DefaultHasher::new();
-        cfg.hash(&mut hasher);
-        let output = dirs::data_dir()
-            .expect("Cannot get user's data directory")
-            .join("openblas_build")
-            .join(format!("{:x}", hasher.finish()));
-        output
-    } else {
-        PathBuf::from(env::var("OUT_DIR").unwrap())
-    };
+    let source = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("source");
+    let output = output_dir(&cfg, &source, "");
+    // Install the libraries and headers as `lib` and `include` for dependent crates
+    cfg.install_prefix = Some(output.join("install"));
 
     // If OpenBLAS is build as shared, user of openblas-src will have to find `libopenblas.so` at runtime.
     //
@@ -142,13 +253,35 @@ fn build() {
     if !feature_enabled("static") {
         println!(
             "cargo:warning=OpenBLAS is built as a shared library. You need to set LD_LIBRARY_PATH={}",
-            output.display()
+            output.join("install/lib").display()
         );
     }
 
-    let source = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("source");
-    let deliv = cfg.build(&source, &output).unwrap();
+    let deliv = cfg.clone().build(&source, &output).unwrap();
+    deliv
+        .write_manifest(output.join("openblas-manifest.toml"))
+        .unwrap();
 
+    // External LAPACK must be linked before OpenBLAS to resolve its BLAS calls by OpenBLAS
+    if let Some(lapack) = &deliv.external_lapack {
+        let path = lapack.path();
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        let name = file_name
+            .trim_start_matches("lib")
+            .split('.')
+            .next()
+            .unwrap();
+        let kind = if file_name.ends_with(".a") {
+            "static"
+        } else {
+            "dylib"
+        };
+        println!(
+            "cargo:rustc-link-search={}",
+            path.parent().unwrap().display()
+        );
+        println!("cargo:rustc-link-lib={}={}", kind, name);
+    }
     for search_path in &deliv.make_conf.c_extra_libs.search_paths {
         println!("cargo:rustc-link-search={}", search_path.display());
     }
@@ -161,7 +294,99 @@ fn build() {
     for lib in &deliv.make_conf.f_extra_libs.libs {
         println!("cargo:rustc-link-lib={}", lib);
     }
-    println!("cargo:rustc-link-search={}", output.display());
+    println!("cargo:rustc-link-search={}", deliv.lib_dir.display());
+    println!("cargo:rustc-link-lib={}={}", link_kind, cfg.lib_name());
+
+    if feature_enabled("ilp64-suffixed") {
+        // Internal symbols of OpenBLAS, e.g. `gotoblas`, are not renamed by the symbol suffix,
+        // and they conflict between two static libraries.
+        if feature_enabled("static") {
+            panic!("ilp64-suffixed feature cannot be used with static feature");
+        }
+        let mut cfg64 = cfg.ilp64_variant();
+        let output64 = output_dir(&cfg64, &source, "ilp64");
+        cfg64.install_prefix = Some(output64.join("install"));
+        println!(
+            "cargo:warning=ILP64 OpenBLAS is built as a shared library. You need to set LD_LIBRARY_PATH={}",
+            output64.join("install/lib").display()
+        );
+        let deliv64 = cfg64.clone().build(&source, &output64).unwrap();
+        deliv64
+            .write_manifest(output64.join("openblas-manifest.toml"))
+            .unwrap();
+        println!("cargo:rustc-link-search={}", deliv64.lib_dir.display());
+        println!("cargo:rustc-link-lib={}={}", link_kind, cfg64.lib_name());
+        // `DEP_OPENBLAS_ILP64_SYMBOL_SUFFIX` for dependent crates
+        metadata(
+            "ilp64_symbol_suffix",
+            cfg64.symbol_suffix.as_deref().unwrap_or(""),
+        );
+    }
+
+    // Published to dependent crates as `DEP_OPENBLAS_*`, e.g. `DEP_OPENBLAS_INCLUDE`
+    let lib = deliv
+        .static_lib
+        .as_ref()
+        .or(deliv.shared_lib.as_ref())
+        .unwrap();
+    if let Some(include) = &deliv.include_dir {
+        metadata("include", include.display());
+    }
+    metadata("lib_dir", deliv.lib_dir.display());
+    metadata(
+        "interface",
+        match cfg.interface {
+            openblas_build::Interface::LP64 => "lp64",
+            openblas_build::Interface::ILP64 => "ilp64",
+        },
+    );
+    metadata("cblas", lib.has_cblas() as i32);
+    metadata("lapacke", lib.has_lapacke() as i32);
+    // OpenBLAS builds threaded library by default if the build machine has multiple cores,
+    // which is found in the library if not specified
+    metadata(
+        "threading",
+        if cfg.use_openmp {
+            "openmp"
+        } else if cfg.threaded().unwrap_or_else(|| lib.has_threading()) {
+            "pthread"
+        } else {
+            "serial"
+        },
+    );
+    // Bind the functions renamed by the symbol affixes
+    metadata("symbol_prefix", cfg.symbol_prefix.as_deref().unwrap_or(""));
+    metadata("symbol_suffix", cfg.symbol_suffix.as_deref().unwrap_or(""));
+}
+
+/// Directory to build OpenBLAS for `cfg`
+///
+/// `name` is a sub-directory of `OUT_DIR` to separate variants, e.g. `ilp64`.
+#[cfg(target_os = "linux")]
+fn output_dir(cfg: &openblas_build::Configure, source: &Path, name: &str) -> PathBuf {
+    if feature_enabled("cache") {
+        // Build OpenBLAS on user's data directory.
+        // See https://docs.rs/dirs/3.0.1/dirs/fn.data_dir.html
+        //
+        // On Linux, `data_dir` returns `$XDG_DATA_HOME` or `$HOME/.local/share`.
+        // This build script creates a directory based on the cache key of `cfg`,
+        // i.e. `$XDG_DATA_HOME/openblas_build/[cache key]`, and build OpenBLAS there.
+        // The key is a stable hash over `cfg`, the target triple, the compilers and the OpenBLAS version.
+        //
+        // This build will be shared among several projects using openblas-src crate.
+        // It makes users not to build OpenBLAS in every `cargo build`.
+        dirs::data_dir()
+            .expect("Cannot get user's data directory")
+            .join("openblas_build")
+            .join(cfg.cache_key(source).unwrap())
+    } else {
+        let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+        if name.is_empty() {
+            out_dir
+        } else {
+            out_dir.join(name)
+        }
+    }
 }
 
 /// openblas-src 0.9.0 compatible `make` runner
@@ -170,10 +395,17 @@ fn build() {
 /// openblas-build crate can detect it by sneaking OpenBLAS build system, but only works on Linux.
 ///
 #[cfg(not(target_os = "linux"))]
-fn build() {
-    use std::fs;
-
+fn build(link_kind: &str) {
     let output = PathBuf::from(env::var("OUT_DIR").unwrap().replace(r"\", "/"));
+    for name in &[
+        "OPENBLAS_ARGS",
+        "OPENBLAS_TARGET",
+        "OPENBLAS_CC",
+        "OPENBLAS_FC",
+        "OPENBLAS_HOSTCC",
+    ] {
+        println!("cargo:rerun-if-env-changed={}", name);
+    }
     let mut make = Command::new("make");
     make.args(&["libs", "netlib", "shared"])
         .arg(format!("BINARY={}", binary()))
@@ -199,8 +431,11 @@ fn build() {
         }
         _ => (),
     };
-    if let Ok(num_jobs) = env::var("NUM_JOBS") {
-        make.arg(format!("-j{}", num_jobs));
+    // Share the jobserver of cargo, and stop sub-makes from running their own `-j` by `MAKE_NB_JOBS=0`
+    if let Ok(makeflags) = env::var("CARGO_MAKEFLAGS") {
+        make.env("MAKEFLAGS", makeflags).arg("MAKE_NB_JOBS=0");
+    } else if let Ok(num_jobs) = env::var("NUM_JOBS") {
+        make.arg(format!("-j{}", num_jobs)).arg("MAKE_NB_JOBS=0");
     }
     let target = match env::var("OPENBLAS_TARGET") {
         Ok(target) => {
@@ -238,6 +473,12 @@ fn build() {
         "cargo:rustc-link-search={}",
         output.join("opt/OpenBLAS/lib").display(),
     );
+    println!("cargo:rustc-link-lib={}=openblas", link_kind);
+    Installed {
+        include: Some(output.join("opt/OpenBLAS/include")),
+        lib_dir: Some(output.join("opt/OpenBLAS/lib")),
+    }
+    .publish();
 
     fn run(command: &mut Command) {
         println!("Running: `{:?}`", command);
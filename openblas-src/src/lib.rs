@@ -8,9 +8,12 @@
 //!
 //! * `cache` to build in `.cargo` instead of `target`,
 //! * `cblas` to build CBLAS (enabled by default),
+//! * `ilp64-suffixed` to build also ILP64 OpenBLAS with `64_` symbol suffix as `libopenblas_64` (Linux only),
 //! * `lapacke` to build LAPACKE (enabled by default),
-//! * `static` to link to OpenBLAS statically, and
-//! * `system` to skip building the bundled OpenBLAS.
+//! * `mode-from-cargo` to build debug or sanitized OpenBLAS following the cargo profile and `-Z sanitizer` (Linux only),
+//! * `static` to link to OpenBLAS statically,
+//! * `system` to skip building the bundled OpenBLAS, and
+//! * `target-from-rustc` to build OpenBLAS for the CPU specified by `-C target-cpu` (Linux only).
 //!
 //! [architecture]: https://blas-lapack-rs.github.io/architecture
 //! [blas]: https://en.wikipedia.org/wiki/BLAS